@@ -0,0 +1,123 @@
+// flow_maps.rs
+//
+// Dijkstra maps toward and away from the player, built once per turn and shared by every monster that needs them.
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use rltk::{BaseMap, DijkstraMap, Point};
+
+use crate::Map;
+
+/// The furthest distance the maps are built out to
+const MAX_DEPTH: f32 = 1000.0;
+
+/// Multiplier applied to the distance to the player to make the flee map. It is negative, since the sign is what turns
+/// the map towards the player in to one away from them, and its magnitude is above one so that monsters prefer to run
+/// past the player to somewhere further away than being backed in to a corner
+const FLEE_FACTOR: f32 = -1.2;
+
+/// Resource for the maps, which are rebuilt when the turn, level, or player's location changes. The flee map is only
+/// needed when someone is running away, so it is built the first time it is asked for.
+#[derive(Default)]
+pub struct FlowMaps {
+    built_for: Option<(u32, i32, Point)>,
+    toward: Option<DijkstraMap>,
+    away: Option<DijkstraMap>,
+}
+
+impl FlowMaps {
+    /// Rebuild the maps if they are not for the turn, level, and player location given
+    pub fn update(&mut self, map: &Map, turn: u32, player: Point) {
+        let key = Some((turn, map.depth, player));
+        if self.built_for == key { return; }
+        self.toward = Some(toward_map(map, player));
+        self.away = None;
+        self.built_for = key;
+    }
+
+    /// Return the next step towards the player, if there is one that gets closer
+    pub fn step_towards(&self, map: &Map, from: Point) -> Option<Point> {
+        let toward = self.toward.as_ref()?;
+        downhill(toward, map, from, value_at(toward, map, from))
+    }
+
+    /// Return the next step away from the player, if there is one that gets further
+    pub fn step_away(&mut self, map: &Map, from: Point) -> Option<Point> {
+        let toward = self.toward.as_ref()?;
+        let away = self.away.get_or_insert_with(|| away_map(map, toward));
+        let here = f32::min(value_at(toward, map, from) * FLEE_FACTOR, value_at(away, map, from));
+        downhill(away, map, from, here)
+    }
+}
+
+/// Build the map of distances to the player
+fn toward_map(map: &Map, player: Point) -> DijkstraMap {
+    let starts = vec![map.xy_idx(player.x, player.y)];
+    DijkstraMap::new(map.width, map.height, &starts, map, MAX_DEPTH)
+}
+
+/// Build the flee map by inverting the distances to the player, then letting the new values flow out from there
+fn away_map(map: &Map, toward: &DijkstraMap) -> DijkstraMap {
+    let mut away = DijkstraMap::new_empty(map.width, map.height, MAX_DEPTH);
+    let mut open = BinaryHeap::new();
+    for (idx, distance) in toward.map.iter().enumerate() {
+        if *distance == f32::MAX { continue; }
+        away.map[idx] = distance * FLEE_FACTOR;
+        open.push(Node { value: away.map[idx], idx });
+    }
+
+    // The starting values vary, so the tiles are visited lowest first to avoid revisiting them as values improve
+    while let Some(Node { value, idx }) = open.pop() {
+        if value > away.map[idx] { continue; }
+        for (exit, cost) in map.get_available_exits(idx) {
+            if value + cost < away.map[exit] {
+                away.map[exit] = value + cost;
+                open.push(Node { value: value + cost, idx: exit });
+            }
+        }
+    }
+    away
+}
+
+/// Entry in the open list when building the flee map, ordered so the lowest value is popped first
+struct Node {
+    value: f32,
+    idx: usize,
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Node {}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Node {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.value.total_cmp(&self.value)
+    }
+}
+
+/// Return the neighbouring tile with the lowest value, provided it is lower than the value of where we are now
+fn downhill(flow: &DijkstraMap, map: &Map, from: Point, here: f32) -> Option<Point> {
+    let next = DijkstraMap::find_lowest_exit(flow, map.xy_idx(from.x, from.y), map)?;
+    if flow.map[next] >= here { return None; }
+    Some(Point::new(next as i32 % map.width, next as i32 / map.width))
+}
+
+/// Return the value of the tile, tiles with someone on them are left out of the map so it is worked out from the
+/// neighbouring tiles instead
+fn value_at(flow: &DijkstraMap, map: &Map, point: Point) -> f32 {
+    let idx = map.xy_idx(point.x, point.y);
+    if flow.map[idx] < f32::MAX { return flow.map[idx]; }
+    map.get_available_exits(idx).iter()
+        .map(|(exit, cost)| flow.map[*exit] + cost)
+        .fold(f32::MAX, f32::min)
+}
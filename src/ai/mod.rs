@@ -1,5 +1,7 @@
 // mod.rs
 //
 // Define what AI is exposed.
+mod flow_maps;
+pub use flow_maps::FlowMaps;
 mod monsters;
 pub use monsters::MonsterAI;
\ No newline at end of file
@@ -1,46 +1,352 @@
 // monsters.ai
 //
-// Define the basic AI for most (dumb) monsters.
-use rltk::{Point};
+// Define the basic AI for most (dumb) monsters, which wander around their post until they spot the player.
+use rltk::{Point, RandomNumberGenerator};
 use specs::prelude::*;
 
-use crate::{Map, Monster, Position, Viewshed};
+use crate::{AiMode, AiState, BlocksVisibility, CombatStats, Door, FlowMaps, GameLog, Map, Monster, MyTurn, OccupiesTile,
+            Position, Renderable, StatusEffects, StatusKind, Viewshed, WantsToMelee};
+
+/// Monsters flee once their health drops to this percentage, or below
+const FLEE_PERCENT: i32 = 25;
+
+/// How far from their post monsters wander
+const WANDER_RADIUS: i32 = 4;
+
+/// The chance, out of six, that a wandering monster moves on a given turn
+const WANDER_CHANCE: i32 = 2;
 
 pub struct MonsterAI { }
 
 impl<'a> System<'a> for MonsterAI {
-    type SystemData = ( WriteExpect<'a, Map>,
+    type SystemData = ( Entities<'a>,
+                        WriteExpect<'a, Map>,
                         ReadExpect<'a, Point>,
+                        ReadExpect<'a, Entity>,
+                        WriteExpect<'a, RandomNumberGenerator>,
+                        WriteExpect<'a, GameLog>,
+                        WriteExpect<'a, FlowMaps>,
                         WriteStorage<'a, Viewshed>,
                         ReadStorage<'a, Monster>,
-                        WriteStorage<'a, Position>);
+                        ReadStorage<'a, StatusEffects>,
+                        ReadStorage<'a, MyTurn>,
+                        ReadStorage<'a, CombatStats>,
+                        WriteStorage<'a, AiState>,
+                        WriteStorage<'a, Position>,
+                        WriteStorage<'a, WantsToMelee>,
+                        WriteStorage<'a, Door>,
+                        WriteStorage<'a, OccupiesTile>,
+                        WriteStorage<'a, BlocksVisibility>,
+                        WriteStorage<'a, Renderable>);
 
     fn run(&mut self, data : Self::SystemData) {
-        let (mut map, player_pos, mut viewshed, monster, mut position) = data;
-
-        for (viewshed, monster, monster_position) in (&mut viewshed, &monster, &mut position).join() {
-            if viewshed.visible_tiles.contains(&*player_pos) {
-                // TODO Swap this out for an attack
-                let distance = rltk::DistanceAlg::Pythagoras.distance2d(Point::new(monster_position.x, monster_position.y), *player_pos);
-                if distance < 1.5 {
-                    println!("{} shouts insults!", monster.name);
-                    return;
+        let (entities, mut map, player_pos, player_entity, mut rng, mut log, mut flow_maps, mut viewshed, monster, statuses, turns,
+             combat_stats, mut ai_states, mut position, mut wants_melee, mut doors, mut occupiers, mut blockers,
+             mut renderables) = data;
+
+        let has = |entity: Entity, kind: StatusKind| statuses.get(entity).is_some_and(|statuses| statuses.has(kind));
+        let mut opened_door = false;
+        let monsters = (&entities, &mut viewshed, &monster, &turns, &mut ai_states, &mut position).join();
+        for (entity, viewshed, monster, _turn, ai, monster_position) in monsters {
+            // Sleeping monsters don't do anything
+            if has(entity, StatusKind::Asleep) { continue; }
+
+            // Confused monsters stumble around at random
+            let here = Point::new(monster_position.x, monster_position.y);
+            if has(entity, StatusKind::Confused) {
+                let (x, y) = (here.x + rng.range(-1, 2), here.y + rng.range(-1, 2));
+                if map.in_bounds(x, y) {
+                    move_to(&mut map, monster_position, viewshed, Point::new(x, y));
+                }
+                continue;
+            }
+
+            // Work out what the monster should be doing
+            let sees_player = viewshed.visible_tiles.contains(&*player_pos);
+            if sees_player {
+                ai.last_seen = Some(*player_pos);
+                let hurt = combat_stats.get(entity).is_some_and(|stats| stats.hp * 100 <= stats.max_hp * FLEE_PERCENT);
+                if hurt && ai.mode != AiMode::Flee {
+                    log.add(format!("{} turns to flee!", monster.name));
+                    ai.mode = AiMode::Flee;
+                } else if !hurt {
+                    ai.mode = AiMode::Chase;
                 }
+            } else if ai.mode == AiMode::Flee {
+                // Out of sight is safe enough to head back
+                ai.mode = AiMode::Return;
+            }
+
+            // Then do it, the maps toward and away from the player are shared by everyone chasing or fleeing this turn
+            if ai.mode == AiMode::Flee || ai.mode == AiMode::Chase {
+                flow_maps.update(&map, log.turn, *player_pos);
+            }
+            let step = match ai.mode {
+                AiMode::Chase => {
+                    let target = ai.last_seen.unwrap_or(*player_pos);
+
+                    // Attack the player if they are adjacent
+                    if sees_player && rltk::DistanceAlg::Pythagoras.distance2d(here, *player_pos) < 1.5 {
+                        wants_melee.insert(entity, WantsToMelee{ target: *player_entity }).expect("Unable to insert attack");
+                        continue;
+                    }
 
-                // Find a path from the monster to the player
-                let path = rltk::a_star_search(
-                    map.xy_idx(monster_position.x, monster_position.y) as i32,
-                    map.xy_idx(player_pos.x, player_pos.y) as i32,
-                    &mut *map
-                );
-
-                // Move in the player's direction by one step
-                if path.success && path.steps.len() > 1 {
-                    monster_position.x = path.steps[1] as i32 % map.width;
-                    monster_position.y = path.steps[1] as i32 / map.width;
-                    viewshed.dirty = true;
+                    // Head for the player, or where they were last seen, giving up once there
+                    let step = if target == *player_pos {
+                        flow_maps.step_towards(&map, here)
+                    } else {
+                        step_towards(&map, here, target)
+                    };
+                    if step.is_none() || (!sees_player && step == Some(target)) {
+                        ai.mode = AiMode::Return;
+                        ai.last_seen = None;
+                    }
+                    step
                 }
+                AiMode::Flee => flow_maps.step_away(&map, here),
+                AiMode::Return => {
+                    let step = step_towards(&map, here, ai.post);
+                    if step.is_none() || step == Some(ai.post) {
+                        ai.mode = AiMode::Wander;
+                    }
+                    step
+                }
+                AiMode::Wander => wander(&map, &mut rng, here, ai.post)
+            };
+            // Open any closed door in the way rather than moving, which takes the turn
+            if let Some(step) = step {
+                let idx = map.xy_idx(step.x, step.y);
+                if map.open_door(idx, &mut doors, &mut occupiers, &mut blockers, &mut renderables) {
+                    if map.visible_tiles[idx] { log.add(format!("{} opens a door.", monster.name)); }
+                    opened_door = true;
+                } else {
+                    move_to(&mut map, monster_position, viewshed, step);
+                }
+            }
+        }
+
+        // Everyone may be able to see through the doorways that were opened
+        if opened_door {
+            for viewshed in (&mut viewshed).join() {
+                viewshed.dirty = true;
+            }
+        }
+    }
+}
+
+/// Move the monster to the point given if nothing else is there, the occupied tiles are updated right away so monsters
+/// that act later in the same pass can't move on to the same tile
+fn move_to(map: &mut Map, position: &mut Position, viewshed: &mut Viewshed, point: Point) {
+    let (from, to) = (map.xy_idx(position.x, position.y), map.xy_idx(point.x, point.y));
+    if map.occupied_tiles[to] { return; }
+    map.occupied_tiles[from] = false;
+    map.occupied_tiles[to] = true;
+    position.x = point.x;
+    position.y = point.y;
+    viewshed.dirty = true;
+}
+
+/// Return the next step on the path from one point to another, if there is a path. Whatever is on the end of the path
+/// doesn't block it, so the last step is refused by move_to and the monster stops one short of anything there
+fn step_towards(map: &Map, from: Point, to: Point) -> Option<Point> {
+    let path = map.find_path(from, to);
+    if path.success && path.steps.len() > 1 {
+        return Some(map.idx_point(path.steps[1]));
+    }
+    None
+}
+
+/// Return a random open neighbouring tile that is close to the post, some turns the monster stays put
+fn wander(map: &Map, rng: &mut RandomNumberGenerator, from: Point, post: Point) -> Option<Point> {
+    if rng.roll_dice(1, 6) > WANDER_CHANCE { return None; }
+    let options: Vec<Point> = open_neighbours(map, from).into_iter()
+        .filter(|point| rltk::DistanceAlg::Chebyshev.distance2d(*point, post) <= WANDER_RADIUS as f32)
+        .collect();
+    rng.random_slice_entry(&options).copied()
+}
+
+/// Return the neighbouring tiles that can be moved in to
+fn open_neighbours(map: &Map, from: Point) -> Vec<Point> {
+    let mut neighbours = Vec::new();
+    for delta_y in -1 ..= 1 {
+        for delta_x in -1 ..= 1 {
+            let (x, y) = (from.x + delta_x, from.y + delta_y);
+            if (delta_x, delta_y) != (0, 0) && map.in_bounds(x, y) && !map.occupied_tiles[map.xy_idx(x, y)] {
+                neighbours.push(Point::new(x, y));
             }
         }
     }
-}
\ No newline at end of file
+    neighbours
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MapIndexingSystem, State, TileType};
+
+    /// Return a new game with only the given number of monsters left, in the order they act
+    fn with_monsters(count: usize) -> (State, Vec<Entity>) {
+        let mut gs = State::for_tests(1234);
+        let monsters = gs.keep_monsters(count);
+        (gs, monsters)
+    }
+
+    /// Return a new game with only one monster left, which has been moved next to the player
+    fn alone_with_one_monster() -> (State, Entity) {
+        let (mut gs, monsters) = with_monsters(1);
+        let beside = beside_player(&gs);
+        place(&mut gs, monsters[0], beside);
+        (gs, monsters[0])
+    }
+
+    /// Return an open floor tile next to the player
+    fn beside_player(gs: &State) -> Point {
+        let player_pos = *gs.ecs.fetch::<Point>();
+        let map = gs.ecs.fetch::<Map>();
+        open_neighbours(&map, player_pos).into_iter()
+            .find(|point| map.tiles[map.xy_idx(point.x, point.y)] == TileType::Floor)
+            .unwrap()
+    }
+
+    /// Return three open floor tiles in a row, out of reach of the player
+    fn open_row(gs: &State) -> [Point; 3] {
+        let player_pos = *gs.ecs.fetch::<Point>();
+        let map = gs.ecs.fetch::<Map>();
+        let open = |point: &Point| map.in_bounds(point.x, point.y)
+            && map.tiles[map.xy_idx(point.x, point.y)] == TileType::Floor
+            && !map.occupied_tiles[map.xy_idx(point.x, point.y)]
+            && rltk::DistanceAlg::Chebyshev.distance2d(*point, player_pos) > 2.0;
+        (0 .. map.height).flat_map(|y| (0 .. map.width).map(move |x| Point::new(x, y)))
+            .map(|point| [Point::new(point.x - 1, point.y), point, Point::new(point.x + 1, point.y)])
+            .find(|row| row.iter().all(open))
+            .unwrap()
+    }
+
+    /// Move the entity to the point given, and index the map
+    fn place(gs: &mut State, entity: Entity, point: Point) {
+        *gs.ecs.write_storage::<Position>().get_mut(entity).unwrap() = Position{ x: point.x, y: point.y };
+        MapIndexingSystem{}.run_now(&gs.ecs);
+    }
+
+    /// Set what the monster is doing, and what it can see, so the visibility system can be left out
+    fn set_mind(gs: &mut State, monster: Entity, mode: AiMode, post: Point, visible_tiles: Vec<Point>) {
+        *gs.ecs.write_storage::<AiState>().get_mut(monster).unwrap() = AiState{ mode, post, last_seen: None };
+        gs.ecs.write_storage::<Viewshed>().get_mut(monster).unwrap().visible_tiles = visible_tiles;
+    }
+
+    /// Give the monsters a turn, and index the map afterwards as the game does
+    fn monsters_turn(gs: &mut State, monsters: &[Entity]) {
+        for monster in monsters {
+            gs.ecs.write_storage::<MyTurn>().insert(*monster, MyTurn{}).unwrap();
+        }
+        MonsterAI{}.run_now(&gs.ecs);
+        gs.ecs.write_storage::<MyTurn>().clear();
+        MapIndexingSystem{}.run_now(&gs.ecs);
+    }
+
+    /// Return where the entity is
+    fn position_of(gs: &State, entity: Entity) -> Point {
+        let positions = gs.ecs.read_storage::<Position>();
+        let pos = positions.get(entity).unwrap();
+        Point::new(pos.x, pos.y)
+    }
+
+    #[test]
+    fn confused_monsters_never_move_on_to_the_player() {
+        let (mut gs, monster) = alone_with_one_monster();
+        StatusEffects::new_effect(&mut gs.ecs.write_storage::<StatusEffects>(), monster, StatusKind::Confused, 1000);
+        let player_pos = *gs.ecs.fetch::<Point>();
+        for _ in 0..500 {
+            monsters_turn(&mut gs, &[monster]);
+            assert_ne!(position_of(&gs, monster), player_pos);
+        }
+    }
+
+    #[test]
+    fn wandering_never_picks_the_players_tile() {
+        let (gs, monster) = alone_with_one_monster();
+        let player_pos = *gs.ecs.fetch::<Point>();
+        let here = position_of(&gs, monster);
+        let map = gs.ecs.fetch::<Map>();
+        assert!(!open_neighbours(&map, here).contains(&player_pos));
+
+        // Even a monster posted on the player's tile has to wander somewhere else
+        let mut rng = RandomNumberGenerator::seeded(1234);
+        for _ in 0..500 {
+            assert_ne!(wander(&map, &mut rng, here, player_pos), Some(player_pos));
+        }
+    }
+
+    #[test]
+    fn monsters_heading_for_the_same_tile_end_up_apart() {
+        let (mut gs, monsters) = with_monsters(2);
+        let [left, middle, right] = open_row(&gs);
+        place(&mut gs, monsters[0], left);
+        place(&mut gs, monsters[1], right);
+        set_mind(&mut gs, monsters[0], AiMode::Return, middle, Vec::new());
+        set_mind(&mut gs, monsters[1], AiMode::Return, middle, Vec::new());
+
+        monsters_turn(&mut gs, &monsters);
+        assert_eq!(position_of(&gs, monsters[0]), middle);
+        assert_eq!(position_of(&gs, monsters[1]), right);
+    }
+
+    #[test]
+    fn an_adjacent_attacker_does_not_stop_the_others() {
+        let (mut gs, monsters) = with_monsters(2);
+        let player_pos = *gs.ecs.fetch::<Point>();
+        let beside = beside_player(&gs);
+        let [left, middle, right] = open_row(&gs);
+        place(&mut gs, monsters[0], beside);
+        place(&mut gs, monsters[1], left);
+        set_mind(&mut gs, monsters[0], AiMode::Chase, beside, vec![player_pos]);
+        set_mind(&mut gs, monsters[1], AiMode::Return, right, Vec::new());
+
+        // The attacker acts first, the monster behind it still gets to move
+        monsters_turn(&mut gs, &monsters);
+        assert!(gs.ecs.read_storage::<WantsToMelee>().contains(monsters[0]));
+        assert_eq!(position_of(&gs, monsters[1]), middle);
+    }
+
+    #[test]
+    fn monsters_can_path_to_the_player() {
+        // The player's tile is occupied, but it is still the end of the path
+        let (gs, monster) = alone_with_one_monster();
+        let player_pos = *gs.ecs.fetch::<Point>();
+        let map = gs.ecs.fetch::<Map>();
+        assert_eq!(step_towards(&map, position_of(&gs, monster), player_pos), Some(player_pos));
+        let [left, _middle, _right] = open_row(&gs);
+        assert!(step_towards(&map, left, player_pos).is_some());
+    }
+
+    #[test]
+    fn chasing_stops_short_of_an_occupied_last_known_position() {
+        let (mut gs, monsters) = with_monsters(2);
+        let [left, middle, right] = open_row(&gs);
+        place(&mut gs, monsters[0], left);
+        place(&mut gs, monsters[1], right);
+        set_mind(&mut gs, monsters[0], AiMode::Chase, left, Vec::new());
+        gs.ecs.write_storage::<AiState>().get_mut(monsters[0]).unwrap().last_seen = Some(right);
+
+        // Only the chaser acts, it keeps going until it reaches the monster stood where the player was
+        monsters_turn(&mut gs, &monsters[..1]);
+        assert_eq!(position_of(&gs, monsters[0]), middle);
+        assert!(gs.ecs.read_storage::<AiState>().get(monsters[0]).unwrap().mode == AiMode::Chase);
+    }
+
+    #[test]
+    fn monsters_open_doors_in_their_way() {
+        let (mut gs, monsters) = with_monsters(1);
+        let [left, middle, right] = open_row(&gs);
+        crate::spawner::door(&mut gs.ecs, middle.x, middle.y);
+        place(&mut gs, monsters[0], left);
+        set_mind(&mut gs, monsters[0], AiMode::Return, right, Vec::new());
+
+        // Opening the door takes the turn, and the way is clear on the next one
+        monsters_turn(&mut gs, &monsters);
+        assert_eq!(position_of(&gs, monsters[0]), left);
+        assert!(gs.ecs.read_storage::<Door>().join().all(|door| door.open));
+        monsters_turn(&mut gs, &monsters);
+        assert_eq!(position_of(&gs, monsters[0]), middle);
+    }
+}
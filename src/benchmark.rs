@@ -0,0 +1,73 @@
+// benchmark.rs
+//
+// Compare the cost of every monster finding its own path to the player against sharing the flow maps.
+use std::time::{Duration, Instant};
+
+use rltk::Point;
+use specs::prelude::*;
+
+use crate::{FlowMaps, Map, Monster, Position, State};
+
+/// Time how long it takes to find the next step to the player for every monster on the level, over the rounds given
+fn time_rounds<F: FnMut(u32)>(rounds: u32, mut round: F) -> Duration {
+    let start = Instant::now();
+    for number in 0..rounds {
+        round(number);
+    }
+    start.elapsed()
+}
+
+/// Entry point for --benchmark, the monsters on the first level all look for their next step towards the player
+pub fn main(gs: State, rounds: &str) {
+    let Ok(rounds) = rounds.parse::<u32>() else {
+        eprintln!("--benchmark expects the number of rounds to run");
+        return;
+    };
+    let map = gs.ecs.fetch::<Map>();
+    let player = *gs.ecs.fetch::<Point>();
+    let monsters: Vec<Point> = (&gs.ecs.read_storage::<Monster>(), &gs.ecs.read_storage::<Position>()).join()
+        .map(|(_monster, pos)| Point::new(pos.x, pos.y))
+        .collect();
+    println!("Map: {}x{}, monsters: {}, rounds: {}", map.width, map.height, monsters.len(), rounds);
+
+    // Every monster searches for a path of its own, as they did before the flow maps
+    let mut found = 0;
+    let a_star = time_rounds(rounds, |_| {
+        for from in monsters.iter() {
+            let path = map.find_path(*from, player);
+            if path.success { found += 1; }
+        }
+    });
+
+    // The maps are built once each round then shared, so always rebuild them to keep the comparison fair
+    let mut flow_maps = FlowMaps::default();
+    let mut stepped = 0;
+    let flow = time_rounds(rounds, |number| {
+        flow_maps.update(&map, number, player);
+        for from in monsters.iter() {
+            if flow_maps.step_towards(&map, *from).is_some() { stepped += 1; }
+        }
+    });
+
+    // Fleeing needs the second map, which is built from the first
+    let mut fled = 0;
+    let flee = time_rounds(rounds, |number| {
+        flow_maps.update(&map, rounds + number, player);
+        for from in monsters.iter() {
+            if flow_maps.step_away(&map, *from).is_some() { fled += 1; }
+        }
+    });
+
+    let per_round = |elapsed: Duration| elapsed.as_secs_f64() * 1000.0 / rounds.max(1) as f64;
+    println!("A* per monster: {:.3} ms per round, {} paths found", per_round(a_star), found);
+    println!("Flow maps: {:.3} ms per round, {} steps found", per_round(flow), stepped);
+    println!("Flow maps with fleeing: {:.3} ms per round, {} steps found", per_round(flee), fled);
+
+    // The timings only compare like with like if both approaches found their way to the player
+    if found == 0 {
+        eprintln!("Warning: A* found no paths to the player, so its timing is not a valid comparison");
+    } else if found != stepped {
+        eprintln!("Warning: A* found {} paths but the flow maps found {} steps, so the two did different work", found,
+                  stepped);
+    }
+}
@@ -0,0 +1,66 @@
+// camera.rs
+//
+// The camera keeps the player in the center of the viewport, only the part of the map inside of it is drawn.
+use rltk::{Point, Rltk, RGB};
+use specs::prelude::*;
+
+use crate::{tile_glyph, Map, Position, Renderable, WINDOW_HEIGHT, WINDOW_WIDTH};
+
+/// Return the map coordinates shown in the upper left corner of the screen
+pub fn viewport_origin(ecs: &World) -> Point {
+    let player_pos = ecs.fetch::<Point>();
+    Point::new(player_pos.x - WINDOW_WIDTH / 2, player_pos.y - WINDOW_HEIGHT / 2)
+}
+
+/// Convert a position on the screen to map coordinates, returns None if it is outside of the viewport
+pub fn screen_to_map(ecs: &World, screen: Point) -> Option<Point> {
+    if screen.x < 0 || screen.x >= WINDOW_WIDTH || screen.y < 0 || screen.y >= WINDOW_HEIGHT {
+        return None;
+    }
+    let origin = viewport_origin(ecs);
+    Some(Point::new(origin.x + screen.x, origin.y + screen.y))
+}
+
+/// Convert map coordinates to a position on the screen, returns None if they are outside of the viewport
+pub fn map_to_screen(ecs: &World, x: i32, y: i32) -> Option<Point> {
+    let origin = viewport_origin(ecs);
+    let screen = Point::new(x - origin.x, y - origin.y);
+    if screen.x < 0 || screen.x >= WINDOW_WIDTH || screen.y < 0 || screen.y >= WINDOW_HEIGHT {
+        return None;
+    }
+    Some(screen)
+}
+
+/// Draw the part of the map in the viewport, and the entities the player can see on it
+pub fn render_camera(ecs: &World, ctx: &mut Rltk) {
+    let map = ecs.fetch::<Map>();
+    let origin = viewport_origin(ecs);
+    let black = RGB::from_f32(0.0, 0.0, 0.0);
+
+    // Only render the tiles that are on the map and that we have seen
+    for screen_y in 0..WINDOW_HEIGHT {
+        for screen_x in 0..WINDOW_WIDTH {
+            let (x, y) = (origin.x + screen_x, origin.y + screen_y);
+            if !map.in_bounds(x, y) { continue; }
+            let idx = map.xy_idx(x, y);
+            if map.revealed_tiles[idx] {
+                let (glyph, fg) = tile_glyph(map.tiles[idx], map.visible_tiles[idx]);
+                ctx.set(screen_x, screen_y, fg, black, glyph);
+            }
+        }
+    }
+
+    // Draw in reverse render order so the most important entity on a tile ends up on top
+    let positions = ecs.read_storage::<Position>();
+    let renderables = ecs.read_storage::<Renderable>();
+    let mut data = (&positions, &renderables).join().collect::<Vec<_>>();
+    data.sort_by_key(|(_pos, render)| std::cmp::Reverse(render.render_order));
+    for (pos, render) in data.iter() {
+        // Only render what the player can actually see
+        let idx = map.xy_idx(pos.x, pos.y);
+        if !map.visible_tiles[idx] { continue; }
+        if let Some(screen) = map_to_screen(ecs, pos.x, pos.y) {
+            ctx.set(screen.x, screen.y, render.fg, render.bg, render.glyph);
+        }
+    }
+}
@@ -1,43 +1,385 @@
 // components.rs
 //
 // Define the components used by NQAR.
-use rltk;
+use std::convert::Infallible as NoError;
+
+use serde::{Deserialize, Serialize};
 use specs::prelude::*;
-use specs_derive::Component;
+use specs::saveload::{ConvertSaveload, Marker, SimpleMarker};
+use specs_derive::{Component, ConvertSaveload};
+
+use crate::{Map, RunStats};
+
+/// Register every component with the world, this needs to be done before any entities are created
+pub fn register_components(ecs: &mut World) {
+    ecs.register::<AiState>();
+    ecs.register::<AreaOfEffect>();
+    ecs.register::<AwardsExperience>();
+    ecs.register::<BlocksVisibility>();
+    ecs.register::<CombatStats>();
+    ecs.register::<Consumable>();
+    ecs.register::<DefenseBonus>();
+    ecs.register::<Door>();
+    ecs.register::<Equippable>();
+    ecs.register::<Equipped>();
+    ecs.register::<Experience>();
+    ecs.register::<HungerClock>();
+    ecs.register::<InBackpack>();
+    ecs.register::<InflictsDamage>();
+    ecs.register::<InflictsStatus>();
+    ecs.register::<Initiative>();
+    ecs.register::<Item>();
+    ecs.register::<MeleePowerBonus>();
+    ecs.register::<Monster>();
+    ecs.register::<MyTurn>();
+    ecs.register::<OccupiesTile>();
+    ecs.register::<Position>();
+    ecs.register::<Ranged>();
+    ecs.register::<Renderable>();
+    ecs.register::<Player>();
+    ecs.register::<ProvidesFood>();
+    ecs.register::<ProvidesHealing>();
+    ecs.register::<SerializationHelper>();
+    ecs.register::<SimpleMarker<SerializeMe>>();
+    ecs.register::<StatusEffects>();
+    ecs.register::<SufferDamage>();
+    ecs.register::<Viewshed>();
+    ecs.register::<WantsToDropItem>();
+    ecs.register::<WantsToMelee>();
+    ecs.register::<WantsToPickupItem>();
+    ecs.register::<WantsToRemoveItem>();
+    ecs.register::<WantsToUseItem>();
+}
+
+/// The modes a monster's AI can be in
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum AiMode { Wander, Chase, Flee, Return }
+
+/// Structure for the state of a monster's AI, the post is where it wanders around and returns to
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct AiState {
+    pub mode: AiMode,
+    pub post: rltk::Point,
+    pub last_seen: Option<rltk::Point>,
+}
+
+/// Structure for items that affect everything within the radius of where they land
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct AreaOfEffect {
+    pub radius: i32,
+}
+
+/// Structure for monsters that are worth experience to the player when they die
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct AwardsExperience {
+    pub xp: i32,
+}
+
+/// Structure for objects that block the view of what is behind them
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct BlocksVisibility { }
+
+/// Structure for the combat statistics of an entity
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct CombatStats {
+    pub max_hp: i32,
+    pub hp: i32,
+    pub defense: i32,
+    pub power: i32,
+}
+
+/// Structure for items that are removed once they have been used
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Consumable { }
+
+/// Structure for doors, which block movement and vision until they are opened
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Door {
+    pub open: bool,
+}
+
+/// Structure for items that make the wearer harder to hurt
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct DefenseBonus {
+    pub defense: i32,
+}
+
+/// The places equipment can be worn, only one item can be worn in each
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum EquipmentSlot { Weapon, Shield, Head, Body, Feet }
+
+impl EquipmentSlot {
+    /// Return the slot matching the name given, if any
+    pub fn from_name(name: &str) -> Option<EquipmentSlot> {
+        match name.to_lowercase().as_str() {
+            "weapon" => Some(EquipmentSlot::Weapon),
+            "shield" => Some(EquipmentSlot::Shield),
+            "head" => Some(EquipmentSlot::Head),
+            "body" => Some(EquipmentSlot::Body),
+            "feet" => Some(EquipmentSlot::Feet),
+            _ => None
+        }
+    }
+
+    /// Return the name of the slot, as shown to the player
+    pub fn name(&self) -> &'static str {
+        match self {
+            EquipmentSlot::Weapon => "Weapon",
+            EquipmentSlot::Shield => "Shield",
+            EquipmentSlot::Head => "Head",
+            EquipmentSlot::Body => "Body",
+            EquipmentSlot::Feet => "Feet",
+        }
+    }
+}
+
+/// Structure for items that can be worn, and the slot they are worn in
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Equippable {
+    pub slot: EquipmentSlot,
+}
+
+/// Structure for items that are being worn, and who is wearing them
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Equipped {
+    pub owner: Entity,
+    pub slot: EquipmentSlot,
+}
+
+/// Structure for the player's level, the experience earned towards the next one, and the perks yet to be picked
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Experience {
+    pub level: i32,
+    pub xp: i32,
+    pub perks_pending: i32,
+}
+
+/// How hungry an entity is, from best to worst
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum HungerState { WellFed, Normal, Hungry, Starving }
+
+impl HungerState {
+    /// Return the name of the state, as shown to the player
+    pub fn name(&self) -> &'static str {
+        match self {
+            HungerState::WellFed => "Well Fed",
+            HungerState::Normal => "Normal",
+            HungerState::Hungry => "Hungry",
+            HungerState::Starving => "Starving",
+        }
+    }
+}
+
+/// Structure for entities that need to eat, the turns are how long until they get hungrier
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct HungerClock {
+    pub state: HungerState,
+    pub turns: i32,
+}
+
+/// Structure for items that damage what they hit
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct InflictsDamage {
+    pub damage: i32,
+}
+
+/// Structure for how quickly an entity acts, and the energy they have built up towards their next turn
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Initiative {
+    pub speed: i32,
+    pub energy: i32,
+}
+
+/// Structure for items that inflict status effects on what they are used on
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct InflictsStatus {
+    pub effects: Vec<StatusEffect>,
+}
+
+/// Structure for items that are being carried, and who is carrying them
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct InBackpack {
+    pub owner: Entity,
+}
+
+/// Structure for item entities
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Item {
+    pub name: String,
+}
+
+/// Structure for items that make the wearer hit harder in melee
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct MeleePowerBonus {
+    pub power: i32,
+}
 
 /// Structure for monster entities
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct Monster {
     pub name: String,
 }
 
+/// Marker for entities that get to act this turn
+#[derive(Component, Debug, Clone)]
+pub struct MyTurn { }
+
 /// Structure for objects that occupy tiles
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct OccupiesTile { }
 
 /// Structure for the player entity
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct Player { }
 
+/// Structure for items that can be eaten, the nutrition is how many turns the eater stays well fed for
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct ProvidesFood {
+    pub nutrition: i32,
+}
+
+/// Structure for items that heal the user
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct ProvidesHealing {
+    pub heal_amount: i32,
+}
+
+/// Structure for items that are used on a target at a distance
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Ranged {
+    pub range: i32,
+}
+
 /// Structure for an entities location
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize, Clone)]
 pub struct Position {
     pub x: i32,
     pub y: i32,
 }
 
 /// Structure for entities that can be rendered
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize, Clone)]
 pub struct Renderable {
     pub glyph: rltk::FontCharType,
     pub fg: rltk::RGB,
     pub bg: rltk::RGB,
+    pub render_order: i32,
+}
+
+/// Marker for entities that should be saved
+pub struct SerializeMe;
+
+/// Structure used to carry the map, and the other resources that are saved, in to and out of a save file
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct SerializationHelper {
+    pub map: Map,
+    pub stats: RunStats,
+    pub turn: u32,
+}
+
+/// The kinds of status effect an entity can be under
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum StatusKind { Confused, Poisoned, Asleep, Hasted }
+
+impl StatusKind {
+    /// Return the name of the status, as shown to the player
+    pub fn name(&self) -> &'static str {
+        match self {
+            StatusKind::Confused => "Confused",
+            StatusKind::Poisoned => "Poisoned",
+            StatusKind::Asleep => "Asleep",
+            StatusKind::Hasted => "Hasted",
+        }
+    }
+}
+
+/// A status effect, and the number of turns it has left
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    pub turns: i32,
+}
+
+/// Structure for the status effects an entity is currently under
+#[derive(Component, Debug, Serialize, Deserialize, Clone, Default)]
+pub struct StatusEffects {
+    pub effects: Vec<StatusEffect>,
+}
+
+impl StatusEffects {
+    /// Returns true if the entity is under the status effect
+    pub fn has(&self, kind: StatusKind) -> bool {
+        self.effects.iter().any(|effect| effect.kind == kind)
+    }
+
+    /// Apply the status effect to the target, creating the component if needed. Reapplying an effect keeps the
+    /// longer of the two durations.
+    pub fn new_effect(store: &mut WriteStorage<StatusEffects>, target: Entity, kind: StatusKind, turns: i32) {
+        if store.get(target).is_none() {
+            store.insert(target, StatusEffects::default()).expect("Unable to insert status effects");
+        }
+        let statuses = store.get_mut(target).unwrap();
+        match statuses.effects.iter_mut().find(|effect| effect.kind == kind) {
+            Some(effect) => effect.turns = i32::max(effect.turns, turns),
+            None => statuses.effects.push(StatusEffect{ kind, turns })
+        }
+    }
+}
+
+/// Structure for damage that has been inflicted on an entity this turn
+#[derive(Component, Debug)]
+pub struct SufferDamage {
+    pub amount: Vec<i32>,
+}
+
+impl SufferDamage {
+    /// Queue damage against the victim, creating the component if needed
+    pub fn new_damage(store: &mut WriteStorage<SufferDamage>, victim: Entity, amount: i32) {
+        if let Some(suffering) = store.get_mut(victim) {
+            suffering.amount.push(amount);
+        } else {
+            let damage = SufferDamage { amount: vec![amount] };
+            store.insert(victim, damage).expect("Unable to insert damage");
+        }
+    }
 }
 
 /// Structure for the viewshed that can be rendered
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize, Clone)]
 pub struct Viewshed {
     pub visible_tiles: Vec<rltk::Point>,
     pub range: i32,
     pub dirty: bool,
+}
+
+/// Structure for an entity's intent to drop an item they are carrying
+#[derive(Component, Debug, Clone)]
+pub struct WantsToDropItem {
+    pub item: Entity,
+}
+
+/// Structure for an entity's intent to attack another in melee
+#[derive(Component, Debug, Clone)]
+pub struct WantsToMelee {
+    pub target: Entity,
+}
+
+/// Structure for an entity's intent to pick up an item
+#[derive(Component, Debug, Clone)]
+pub struct WantsToPickupItem {
+    pub collected_by: Entity,
+    pub item: Entity,
+}
+
+/// Structure for an entity's intent to take off an item they are wearing
+#[derive(Component, Debug, Clone)]
+pub struct WantsToRemoveItem {
+    pub item: Entity,
+}
+
+/// Structure for an entity's intent to use an item they are carrying, ranged items need a target
+#[derive(Component, Debug, Clone)]
+pub struct WantsToUseItem {
+    pub item: Entity,
+    pub target: Option<rltk::Point>,
 }
\ No newline at end of file
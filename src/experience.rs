@@ -0,0 +1,150 @@
+// experience.rs
+//
+// Experience earned by the player, the levels it leads to, and the perks picked along the way.
+use specs::prelude::*;
+
+use crate::{CombatStats, Experience, GameLog, Initiative, Viewshed};
+
+/// The experience needed to leave each level is this many times the level
+const LEVEL_XP: i32 = 50;
+
+/// Every level adds this much to the player's maximum health, and they are healed in full
+const LEVEL_HP: i32 = 5;
+
+/// The perks the player can pick from each time they level up
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Perk { Toughness, Might, Guard, FarSight, Swiftness }
+
+impl Perk {
+    pub const ALL: [Perk; 5] = [Perk::Toughness, Perk::Might, Perk::Guard, Perk::FarSight, Perk::Swiftness];
+
+    /// Return the name of the perk, as shown to the player
+    pub fn name(&self) -> &'static str {
+        match self {
+            Perk::Toughness => "Toughness",
+            Perk::Might => "Might",
+            Perk::Guard => "Guard",
+            Perk::FarSight => "Far Sight",
+            Perk::Swiftness => "Swiftness",
+        }
+    }
+
+    /// Return what the perk does, as shown to the player
+    pub fn description(&self) -> &'static str {
+        match self {
+            Perk::Toughness => "+10 maximum hp",
+            Perk::Might => "+1 power",
+            Perk::Guard => "+1 defense",
+            Perk::FarSight => "+2 vision",
+            Perk::Swiftness => "+10 speed",
+        }
+    }
+}
+
+/// Return the experience needed to go from the level given to the next
+pub fn xp_to_next_level(level: i32) -> i32 {
+    LEVEL_XP * level
+}
+
+/// Give the player experience, levelling them up as many times as it is enough for
+pub fn award_experience(ecs: &mut World, xp: i32) {
+    let player_entity = *ecs.fetch::<Entity>();
+    let mut experience = ecs.write_storage::<Experience>();
+    let mut combat_stats = ecs.write_storage::<CombatStats>();
+    let mut log = ecs.write_resource::<GameLog>();
+    let Some(experience) = experience.get_mut(player_entity) else { return };
+
+    experience.xp += xp;
+    while experience.xp >= xp_to_next_level(experience.level) {
+        experience.xp -= xp_to_next_level(experience.level);
+        experience.level += 1;
+        experience.perks_pending += 1;
+        if let Some(stats) = combat_stats.get_mut(player_entity) {
+            stats.max_hp += LEVEL_HP;
+            stats.hp = stats.max_hp;
+            stats.power += 1;
+        }
+        log.add(format!("Welcome to level {}, you feel stronger!", experience.level));
+    }
+}
+
+/// Apply the perk the player picked, using up one of the perks they are owed
+pub fn apply_perk(ecs: &mut World, perk: Perk) {
+    let player_entity = *ecs.fetch::<Entity>();
+    {
+        let mut experience = ecs.write_storage::<Experience>();
+        let mut combat_stats = ecs.write_storage::<CombatStats>();
+        let mut viewsheds = ecs.write_storage::<Viewshed>();
+        let mut initiatives = ecs.write_storage::<Initiative>();
+        if let Some(experience) = experience.get_mut(player_entity) {
+            experience.perks_pending = i32::max(0, experience.perks_pending - 1);
+        }
+        let stats = combat_stats.get_mut(player_entity);
+        match perk {
+            Perk::Toughness => if let Some(stats) = stats {
+                stats.max_hp += 10;
+                stats.hp += 10;
+            }
+            Perk::Might => if let Some(stats) = stats { stats.power += 1; }
+            Perk::Guard => if let Some(stats) = stats { stats.defense += 1; }
+            Perk::FarSight => if let Some(viewshed) = viewsheds.get_mut(player_entity) {
+                viewshed.range += 2;
+                viewshed.dirty = true;
+            }
+            Perk::Swiftness => if let Some(initiative) = initiatives.get_mut(player_entity) { initiative.speed += 10; }
+        }
+    }
+    ecs.write_resource::<GameLog>().add(format!("You gain {} ({}).", perk.name(), perk.description()));
+}
+
+/// Returns the number of perks the player has earned but not picked yet
+pub fn perks_pending(ecs: &World) -> i32 {
+    let player_entity = *ecs.fetch::<Entity>();
+    ecs.read_storage::<Experience>().get(player_entity).map_or(0, |experience| experience.perks_pending)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::State;
+
+    /// Return the player's experience and combat statistics
+    fn player_progress(gs: &State) -> (Experience, CombatStats) {
+        let player_entity = *gs.ecs.fetch::<Entity>();
+        let experience = gs.ecs.read_storage::<Experience>().get(player_entity).cloned().unwrap();
+        let stats = gs.ecs.read_storage::<CombatStats>().get(player_entity).cloned().unwrap();
+        (experience, stats)
+    }
+
+    #[test]
+    fn levels_up_on_reaching_the_threshold() {
+        let mut gs = State::for_tests(1234);
+        let (_experience, start) = player_progress(&gs);
+
+        award_experience(&mut gs.ecs, xp_to_next_level(1) - 1);
+        let (experience, _stats) = player_progress(&gs);
+        assert_eq!((experience.level, experience.xp, experience.perks_pending), (1, xp_to_next_level(1) - 1, 0));
+
+        award_experience(&mut gs.ecs, 1);
+        let (experience, stats) = player_progress(&gs);
+        assert_eq!((experience.level, experience.xp, experience.perks_pending), (2, 0, 1));
+        let levelled_hp = start.max_hp + LEVEL_HP;
+        assert_eq!((stats.max_hp, stats.hp, stats.power), (levelled_hp, levelled_hp, start.power + 1));
+    }
+
+    #[test]
+    fn levels_up_several_times_at_once() {
+        let mut gs = State::for_tests(1234);
+        let (_experience, start) = player_progress(&gs);
+
+        award_experience(&mut gs.ecs, xp_to_next_level(1) + xp_to_next_level(2) + 10);
+        let (experience, stats) = player_progress(&gs);
+        assert_eq!((experience.level, experience.xp, experience.perks_pending), (3, 10, 2));
+        assert_eq!((stats.max_hp, stats.power), (start.max_hp + 2 * LEVEL_HP, start.power + 2));
+
+        // Each perk picked uses up one of those owed
+        apply_perk(&mut gs.ecs, Perk::Toughness);
+        assert_eq!(perks_pending(&gs.ecs), 1);
+        assert_eq!(player_progress(&gs).1.max_hp, start.max_hp + 2 * LEVEL_HP + 10);
+    }
+}
@@ -0,0 +1,36 @@
+// gamelog.rs
+//
+// Define the log of messages shown to the player, and the statistics kept for the run.
+use serde::{Deserialize, Serialize};
+
+/// A single message, stamped with the turn it happened on
+pub struct LogEntry {
+    pub turn: u32,
+    pub message: String,
+}
+
+/// Resource for the messages generated over the course of the game
+#[derive(Default)]
+pub struct GameLog {
+    pub turn: u32,
+    pub entries: Vec<LogEntry>,
+}
+
+impl GameLog {
+    /// Add the message to the log for the current turn
+    pub fn add<S: Into<String>>(&mut self, message: S) {
+        self.entries.push(LogEntry { turn: self.turn, message: message.into() });
+    }
+
+    /// Return the most recent entries, newest first
+    pub fn recent(&self, count: usize) -> impl Iterator<Item = &LogEntry> {
+        self.entries.iter().rev().take(count)
+    }
+}
+
+/// Resource for the statistics shown when the run comes to an end
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub struct RunStats {
+    pub monsters_killed: u32,
+    pub items_used: u32,
+}
@@ -0,0 +1,336 @@
+// gui.rs
+//
+// Draw the user interface, the panel at the bottom of the screen and any menus.
+use rltk::{Point, Rltk, VirtualKeyCode, RGB};
+use specs::prelude::*;
+
+use crate::{camera, key_direction, valid_targets, xp_to_next_level, CombatStats, Door, Experience, GameLog, HungerClock,
+            HungerState, Item, Map, Monster, Perk, Player, Position, RunStats, StatusEffects, StatusKind, WINDOW_HEIGHT,
+            WINDOW_WIDTH};
+
+/// The height of the panel, which fills the screen below the map
+pub const PANEL_HEIGHT: i32 = 7;
+
+/// Draw the status and message log panel
+pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
+    let white = RGB::named(rltk::WHITE);
+    let black = RGB::named(rltk::BLACK);
+    let yellow = RGB::named(rltk::YELLOW);
+    ctx.draw_box(0, WINDOW_HEIGHT, WINDOW_WIDTH, PANEL_HEIGHT - 1, white, black);
+
+    // Depth of the current map
+    let map = ecs.fetch::<Map>();
+    ctx.print_color(2, WINDOW_HEIGHT, yellow, black, format!(" Depth: {} ", map.depth));
+
+    // Health of the player
+    let combat_stats = ecs.read_storage::<CombatStats>();
+    let players = ecs.read_storage::<Player>();
+    for (_player, stats) in (&players, &combat_stats).join() {
+        let health = format!(" HP: {} / {} ", stats.hp, stats.max_hp);
+        ctx.print_color(14, WINDOW_HEIGHT, yellow, black, &health);
+        ctx.draw_bar_horizontal(30, WINDOW_HEIGHT, 17, stats.hp, stats.max_hp, RGB::named(rltk::RED), black);
+    }
+
+    // Level of the player, and how far they are towards the next one
+    let experience = ecs.read_storage::<Experience>();
+    for (_player, experience) in (&players, &experience).join() {
+        let progress = format!(" XP: {} / {} ", experience.xp, xp_to_next_level(experience.level));
+        ctx.print_color(48, WINDOW_HEIGHT, yellow, black, format!(" Level: {} ", experience.level));
+        ctx.print_color(59, WINDOW_HEIGHT, yellow, black, &progress);
+    }
+
+    // Status effects the player is under, along the bottom of the panel
+    let statuses = ecs.read_storage::<StatusEffects>();
+    let mut x = 2;
+    for (_player, statuses) in (&players, &statuses).join() {
+        for effect in statuses.effects.iter() {
+            let colour = match effect.kind {
+                StatusKind::Confused => rltk::MAGENTA,
+                StatusKind::Poisoned => rltk::GREEN,
+                StatusKind::Asleep => rltk::LIGHT_BLUE,
+                StatusKind::Hasted => rltk::YELLOW,
+            };
+            let indicator = format!(" {} ({}) ", effect.kind.name(), effect.turns);
+            ctx.print_color(x, WINDOW_HEIGHT + PANEL_HEIGHT - 1, RGB::named(colour), black, &indicator);
+            x += indicator.len() as i32;
+        }
+    }
+
+    // How hungry the player is, at the other end of the bottom of the panel and only once it matters
+    let clocks = ecs.read_storage::<HungerClock>();
+    for (_player, clock) in (&players, &clocks).join() {
+        let colour = match clock.state {
+            HungerState::WellFed => rltk::GREEN,
+            HungerState::Normal => continue,
+            HungerState::Hungry => rltk::ORANGE,
+            HungerState::Starving => rltk::RED,
+        };
+        let indicator = format!(" {} ", clock.state.name());
+        let x = WINDOW_WIDTH - 1 - indicator.len() as i32;
+        ctx.print_color(x, WINDOW_HEIGHT + PANEL_HEIGHT - 1, RGB::named(colour), black, &indicator);
+    }
+
+    // Most recent messages, newest at the top
+    let log = ecs.fetch::<GameLog>();
+    for (ndx, entry) in log.recent((PANEL_HEIGHT - 2) as usize).enumerate() {
+        ctx.print(2, WINDOW_HEIGHT + 1 + ndx as i32, format!("[{:>4}] {}", entry.turn, entry.message));
+    }
+}
+
+/// Describe what is on the tile under the mouse, provided the player can see it
+pub fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
+    let Some(mouse) = camera::screen_to_map(ecs, Point::from_tuple(ctx.mouse_pos())) else { return };
+    let map = ecs.fetch::<Map>();
+    if !map.in_bounds(mouse.x, mouse.y) || !map.visible_tiles[map.xy_idx(mouse.x, mouse.y)] { return; }
+
+    // Everything on the tile, followed by the tile itself
+    let monsters = ecs.read_storage::<Monster>();
+    let items = ecs.read_storage::<Item>();
+    let doors = ecs.read_storage::<Door>();
+    let players = ecs.read_storage::<Player>();
+    let positions = ecs.read_storage::<Position>();
+    let entities = ecs.entities();
+    let mut lines: Vec<String> = Vec::new();
+    for (entity, position) in (&entities, &positions).join() {
+        if position.x != mouse.x || position.y != mouse.y { continue; }
+        if players.get(entity).is_some() {
+            lines.push("You".to_string());
+        } else if let Some(monster) = monsters.get(entity) {
+            lines.push(monster.name.clone());
+        } else if let Some(item) = items.get(entity) {
+            lines.push(item.name.clone());
+        } else if let Some(door) = doors.get(entity) {
+            lines.push(if door.open { "Open door" } else { "Closed door" }.to_string());
+        }
+    }
+    lines.push(map.tiles[map.xy_idx(mouse.x, mouse.y)].name().to_string());
+
+    // Put the tooltip on whichever side of the mouse has more room, keeping it above the panel
+    let (mouse_x, mouse_y) = ctx.mouse_pos();
+    ctx.set_bg(mouse_x, mouse_y, RGB::named(rltk::MAGENTA));
+    let width = lines.iter().map(|line| line.len()).max().unwrap_or(0) as i32 + 2;
+    let x = if mouse_x > WINDOW_WIDTH / 2 { mouse_x - width - 1 } else { mouse_x + 2 };
+    let y = mouse_y.min(WINDOW_HEIGHT - lines.len() as i32);
+    let (white, grey) = (RGB::named(rltk::WHITE), RGB::named(rltk::DIM_GREY));
+    for (ndx, line) in lines.iter().enumerate() {
+        ctx.print_color(x, y + ndx as i32, white, grey, format!(" {:<width$} ", line, width = width as usize - 2));
+    }
+}
+
+/// The outcome of showing a menu to the player
+#[derive(PartialEq, Copy, Clone)]
+pub enum ItemMenuResult { Cancel, NoResponse, Selected }
+
+/// Return the left edge and first row of a menu box with the width and number of rows given, the box is centered over
+/// the map, and kept below the top of the screen however many rows there are
+fn menu_origin(width: i32, rows: i32) -> Point {
+    Point::new((WINDOW_WIDTH - width) / 2, i32::max(2, (WINDOW_HEIGHT - rows) / 2))
+}
+
+/// Show the items given, either carried or worn by the player, and return the index of the one selected
+pub fn show_inventory(ecs: &World, ctx: &mut Rltk, title: &str, inventory: &[Entity])
+        -> (ItemMenuResult, Option<usize>) {
+    let white = RGB::named(rltk::WHITE);
+    let black = RGB::named(rltk::BLACK);
+    let yellow = RGB::named(rltk::YELLOW);
+
+    // Draw the box with the items in it
+    let items = ecs.read_storage::<Item>();
+    let count = inventory.len() as i32;
+    let Point { x: left, y: top } = menu_origin(31, count);
+    ctx.draw_box(left, top - 2, 31, count + 3, white, black);
+    ctx.print_color(left + 3, top - 2, yellow, black, title);
+    ctx.print_color(left + 3, top + count + 1, yellow, black, "ESCAPE to cancel");
+
+    for (ndx, item) in inventory.iter().enumerate() {
+        let y = top + ndx as i32;
+        ctx.set(left + 2, y, white, black, rltk::to_cp437('('));
+        ctx.set(left + 3, y, yellow, black, 97 + ndx as rltk::FontCharType);
+        ctx.set(left + 4, y, white, black, rltk::to_cp437(')'));
+        ctx.print(left + 6, y, &items.get(*item).unwrap().name);
+    }
+
+    // Check for a selection
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(VirtualKeyCode::Escape) => (ItemMenuResult::Cancel, None),
+        Some(key) => {
+            let selection = rltk::letter_to_option(key);
+            if selection > -1 && selection < count {
+                return (ItemMenuResult::Selected, Some(selection as usize));
+            }
+            (ItemMenuResult::NoResponse, None)
+        }
+    }
+}
+
+/// Let the player pick a tile in range to use a ranged item on, returns the cursor, which is the target once selected
+pub fn ranged_target(ecs: &World, ctx: &mut Rltk, range: i32, radius: i32, cursor: Point) -> (ItemMenuResult, Point) {
+    let black = RGB::named(rltk::BLACK);
+    let yellow = RGB::named(rltk::YELLOW);
+    ctx.print_color(5, 0, yellow, black, "Select Target: move to aim, ENTER or click to fire, ESCAPE to cancel");
+
+    // Highlight the tiles that can be targeted
+    let targets = valid_targets(ecs, range);
+    for tile in targets.iter() {
+        if let Some(screen) = camera::map_to_screen(ecs, tile.x, tile.y) {
+            ctx.set_bg(screen.x, screen.y, RGB::named(rltk::BLUE));
+        }
+    }
+
+    // Show where the shot would land, and what it would catch in the blast
+    let mouse = camera::screen_to_map(ecs, Point::from_tuple(ctx.mouse_pos()));
+    let aim = match mouse {
+        Some(mouse) if targets.contains(&mouse) => mouse,
+        _ => cursor
+    };
+    if targets.contains(&aim) {
+        let map = ecs.fetch::<Map>();
+        let impact = map.line_of_fire(*ecs.fetch::<Point>(), aim);
+        let mut blast = vec![impact];
+        if radius > 0 {
+            blast = rltk::field_of_view(impact, radius, &*map);
+        }
+        for tile in blast.iter() {
+            if let Some(screen) = camera::map_to_screen(ecs, tile.x, tile.y) {
+                ctx.set_bg(screen.x, screen.y, RGB::named(rltk::ORANGE));
+            }
+        }
+    }
+    if let Some(screen) = camera::map_to_screen(ecs, cursor.x, cursor.y) {
+        let colour = if targets.contains(&cursor) { rltk::CYAN } else { rltk::RED };
+        ctx.set_bg(screen.x, screen.y, RGB::named(colour));
+    }
+
+    // A click fires at the tile under the mouse
+    if ctx.left_click {
+        return match mouse {
+            Some(mouse) if targets.contains(&mouse) => (ItemMenuResult::Selected, mouse),
+            _ => (ItemMenuResult::NoResponse, cursor)
+        };
+    }
+
+    // The keyboard moves the cursor, and fires at it
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, cursor),
+        Some(VirtualKeyCode::Escape) => (ItemMenuResult::Cancel, cursor),
+        Some(VirtualKeyCode::Return) | Some(VirtualKeyCode::NumpadEnter) if targets.contains(&cursor) =>
+            (ItemMenuResult::Selected, cursor),
+        Some(key) => match key_direction(key) {
+            Some((delta_x, delta_y)) => (ItemMenuResult::NoResponse, Point::new(cursor.x + delta_x, cursor.y + delta_y)),
+            None => (ItemMenuResult::NoResponse, cursor)
+        }
+    }
+}
+
+/// The options on the main menu
+#[derive(PartialEq, Copy, Clone)]
+pub enum MainMenuSelection { NewGame, Continue, Quit }
+
+/// The outcome of showing the main menu, the selection is what is highlighted until one is picked
+#[derive(PartialEq, Copy, Clone)]
+pub enum MainMenuResult { NoSelection { selected: MainMenuSelection }, Selected { selected: MainMenuSelection } }
+
+/// Show the main menu, continue is only offered when there is a saved game, and why the last one couldn't be loaded
+pub fn main_menu(ctx: &mut Rltk, selection: MainMenuSelection, can_continue: bool, load_error: Option<&str>)
+        -> MainMenuResult {
+    let black = RGB::named(rltk::BLACK);
+    let white = RGB::named(rltk::WHITE);
+    let yellow = RGB::named(rltk::YELLOW);
+    let magenta = RGB::named(rltk::MAGENTA);
+    ctx.print_color_centered(15, yellow, black, "NQAR");
+    ctx.print_color_centered(16, white, black, "Not Quite A Roguelike");
+    if let Some(error) = load_error {
+        let red = RGB::named(rltk::RED);
+        ctx.print_color_centered(17, red, black, "The saved game could not be continued:");
+        ctx.print_color_centered(18, red, black, error);
+    }
+
+    let mut options = vec![(MainMenuSelection::NewGame, "New Game")];
+    if can_continue {
+        options.push((MainMenuSelection::Continue, "Continue"));
+    }
+    options.push((MainMenuSelection::Quit, "Quit"));
+    for (ndx, (option, label)) in options.iter().enumerate() {
+        let colour = if *option == selection { magenta } else { white };
+        ctx.print_color_centered(20 + ndx as i32, colour, black, label);
+    }
+
+    // The arrow keys, or vi-keys, move the selection and wrap around
+    let current = options.iter().position(|(option, _)| *option == selection).unwrap_or(0);
+    let step = |delta: usize| options[(current + delta) % options.len()].0;
+    match ctx.key {
+        Some(VirtualKeyCode::Escape) => MainMenuResult::Selected { selected: MainMenuSelection::Quit },
+        Some(VirtualKeyCode::Up) | Some(VirtualKeyCode::K) =>
+            MainMenuResult::NoSelection { selected: step(options.len() - 1) },
+        Some(VirtualKeyCode::Down) | Some(VirtualKeyCode::J) =>
+            MainMenuResult::NoSelection { selected: step(1) },
+        Some(VirtualKeyCode::Return) | Some(VirtualKeyCode::NumpadEnter) =>
+            MainMenuResult::Selected { selected: options[current].0 },
+        _ => MainMenuResult::NoSelection { selected: options[current].0 }
+    }
+}
+
+/// The outcome of showing the level up screen, the selection is the perk highlighted until one is picked
+#[derive(PartialEq, Copy, Clone)]
+pub enum LevelUpResult { NoSelection { selected: usize }, Selected { selected: Perk } }
+
+/// Show the perks the player can pick from after levelling up, there is no way to cancel
+pub fn level_up(ecs: &World, ctx: &mut Rltk, selection: usize) -> LevelUpResult {
+    let black = RGB::named(rltk::BLACK);
+    let white = RGB::named(rltk::WHITE);
+    let yellow = RGB::named(rltk::YELLOW);
+    let magenta = RGB::named(rltk::MAGENTA);
+
+    let count = Perk::ALL.len() as i32;
+    let Point { x: left, y: top } = menu_origin(40, count);
+    ctx.draw_box(left, top - 2, 40, count + 3, white, black);
+    let player_entity = *ecs.fetch::<Entity>();
+    let level = ecs.read_storage::<Experience>().get(player_entity).map_or(1, |experience| experience.level);
+    ctx.print_color(left + 3, top - 2, yellow, black, format!("Level {}, Pick a Perk", level));
+    ctx.print_color(left + 3, top + count + 1, yellow, black, "ENTER to pick");
+    for (ndx, perk) in Perk::ALL.iter().enumerate() {
+        let colour = if ndx == selection { magenta } else { white };
+        let line = format!("{:<10} {}", perk.name(), perk.description());
+        ctx.print_color(left + 2, top + ndx as i32, colour, black, line);
+    }
+
+    // The arrow keys, or vi-keys, move the selection and wrap around
+    let step = |delta: usize| (selection + delta) % Perk::ALL.len();
+    match ctx.key {
+        Some(VirtualKeyCode::Up) | Some(VirtualKeyCode::K) =>
+            LevelUpResult::NoSelection { selected: step(Perk::ALL.len() - 1) },
+        Some(VirtualKeyCode::Down) | Some(VirtualKeyCode::J) =>
+            LevelUpResult::NoSelection { selected: step(1) },
+        Some(VirtualKeyCode::Return) | Some(VirtualKeyCode::NumpadEnter) =>
+            LevelUpResult::Selected { selected: Perk::ALL[selection] },
+        _ => LevelUpResult::NoSelection { selected: selection }
+    }
+}
+
+/// The outcome of showing the game over screen
+#[derive(PartialEq, Copy, Clone)]
+pub enum GameOverResult { NoSelection, Dismissed }
+
+/// Show how the run went, and the seed it was played with, any key returns to the main menu
+pub fn game_over(ecs: &World, ctx: &mut Rltk, seed: u64) -> GameOverResult {
+    let black = RGB::named(rltk::BLACK);
+    let white = RGB::named(rltk::WHITE);
+    let yellow = RGB::named(rltk::YELLOW);
+    let stats = ecs.fetch::<RunStats>();
+    ctx.print_color_centered(15, yellow, black, "Your journey has ended!");
+    ctx.print_color_centered(18, white, black, format!("You survived for {} turns.", ecs.fetch::<GameLog>().turn));
+    ctx.print_color_centered(19, white, black, format!("You reached depth {}.", ecs.fetch::<Map>().depth));
+    let player_entity = *ecs.fetch::<Entity>();
+    let level = ecs.read_storage::<Experience>().get(player_entity).map_or(1, |experience| experience.level);
+    ctx.print_color_centered(20, white, black, format!("You reached level {}.", level));
+    ctx.print_color_centered(21, white, black, format!("You killed {} monsters.", stats.monsters_killed));
+    ctx.print_color_centered(22, white, black, format!("You used {} items.", stats.items_used));
+    ctx.print_color_centered(24, white, black, format!("The dungeon's seed was {}.", seed));
+    ctx.print_color_centered(26, RGB::named(rltk::MAGENTA), black, "Press any key to return to the main menu");
+
+    match ctx.key {
+        None => GameOverResult::NoSelection,
+        Some(_) => GameOverResult::Dismissed
+    }
+}
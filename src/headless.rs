@@ -0,0 +1,159 @@
+// headless.rs
+//
+// Driver for running the game without a window, stepping the systems with scripted input.
+use specs::prelude::*;
+
+use crate::{apply_perk, backpack_items, default_target, equipped_items, perks_pending, player_action,
+            xp_to_next_level, Action, CombatStats, Equipped, Experience, GameLog, HungerClock, Item, Map, MapStats,
+            Monster, Perk, Player, Position, RunState, State, StatusEffects};
+
+/// Return the action for a single script command, moves use the vi-keys layout
+fn parse_command(command: char) -> Option<Action> {
+    match command {
+        'h' => Some(Action::Move(-1, 0)),
+        'l' => Some(Action::Move(1, 0)),
+        'k' => Some(Action::Move(0, -1)),
+        'j' => Some(Action::Move(0, 1)),
+        'y' => Some(Action::Move(-1, -1)),
+        'u' => Some(Action::Move(1, -1)),
+        'b' => Some(Action::Move(-1, 1)),
+        'n' => Some(Action::Move(1, 1)),
+        '.' => Some(Action::Wait),
+        '>' => Some(Action::Descend),
+        'g' => Some(Action::Pickup),
+        _ => None
+    }
+}
+
+/// Parse the script in to the actions to take, whitespace is ignored
+pub fn parse_script(script: &str) -> Result<Vec<Action>, String> {
+    let mut actions = Vec::new();
+    let mut commands = script.chars().enumerate().filter(|(_ndx, command)| !command.is_whitespace());
+    while let Some((ndx, command)) = commands.next() {
+        // Item commands are followed by the inventory slot to use
+        if command == 'i' || command == 'd' || command == 'r' {
+            let slot = commands.next().and_then(|(_ndx, slot)| slot.to_digit(10));
+            let Some(slot) = slot else {
+                return Err(format!("Command '{}' at position {} expects an inventory slot", command, ndx));
+            };
+            actions.push(match command {
+                'i' => Action::UseItem(slot as usize),
+                'd' => Action::DropItem(slot as usize),
+                _ => Action::RemoveItem(slot as usize)
+            });
+            continue;
+        }
+
+        match parse_command(command) {
+            Some(action) => actions.push(action),
+            None => return Err(format!("Unknown command '{}' at position {}", command, ndx))
+        }
+    }
+    Ok(actions)
+}
+
+/// Run the systems if the last action used up the turn, mirroring GameState::tick
+fn resolve_turn(gs: &mut State) {
+    // There is no one to aim, so ranged items are used on the closest monster in range
+    if let RunState::ShowTargeting { slot, range, .. } = gs.run_state {
+        gs.run_state = match default_target(&gs.ecs, range) {
+            Some(target) => player_action(gs, Action::UseItemAt(slot, target)),
+            None => {
+                gs.ecs.write_resource::<GameLog>().add("There is nothing in range to target.");
+                RunState::Paused
+            }
+        };
+    }
+    if gs.run_state == RunState::NextLevel {
+        gs.goto_next_level();
+        gs.run_state = RunState::Running;
+    }
+    if gs.run_state == RunState::Running {
+        gs.run_until_player_turn();
+        gs.run_state = RunState::Paused;
+    }
+
+    // There is no one to pick the perks either, so they are taken in turn by level
+    while perks_pending(&gs.ecs) > 0 {
+        let player_entity = *gs.ecs.fetch::<Entity>();
+        let level = gs.ecs.read_storage::<Experience>().get(player_entity).map_or(1, |experience| experience.level);
+        apply_perk(&mut gs.ecs, Perk::ALL[level as usize % Perk::ALL.len()]);
+    }
+}
+
+/// Print any log entries after the first number given, and return the new number of entries
+fn print_log(gs: &State, printed: usize) -> usize {
+    let log = gs.ecs.fetch::<GameLog>();
+    for entry in log.entries.iter().skip(printed) {
+        println!("[{:>4}] {}", entry.turn, entry.message);
+    }
+    log.entries.len()
+}
+
+/// Apply the actions to the world in order, stopping early if the player dies, and return the turns taken
+pub fn run(gs: &mut State, actions: &[Action]) -> usize {
+    // Let the world settle before the first action, as the first tick does
+    resolve_turn(gs);
+    let mut printed = print_log(gs, 0);
+
+    let mut turns = 0;
+    for action in actions {
+        if !gs.player_alive() { break; }
+        gs.run_state = player_action(gs, *action);
+        resolve_turn(gs);
+        printed = print_log(gs, printed);
+        turns += 1;
+    }
+    turns
+}
+
+/// Print a summary of the world, intended to be easy to compare between runs
+pub fn report(gs: &State, turns: usize) {
+    let players = gs.ecs.read_storage::<Player>();
+    let monsters = gs.ecs.read_storage::<Monster>();
+    let positions = gs.ecs.read_storage::<Position>();
+    let stats = gs.ecs.read_storage::<CombatStats>();
+
+    println!("Turns: {}", turns);
+    let map = gs.ecs.fetch::<Map>();
+    let layout = gs.ecs.fetch::<MapStats>();
+    println!("Depth: {}", map.depth);
+    println!("Map: {} rooms, {:.1}% of the floor reachable, {} regions connected, {} culled", layout.rooms,
+             layout.reachable_ratio() * 100.0, layout.connected_regions, layout.culled_regions);
+    for (_player, pos, stats) in (&players, &positions, &stats).join() {
+        println!("Player: ({}, {}) hp {}/{}", pos.x, pos.y, stats.hp, stats.max_hp);
+    }
+    let experience = gs.ecs.read_storage::<Experience>();
+    for (_player, experience) in (&players, &experience).join() {
+        println!("Level: {} ({} / {} xp)", experience.level, experience.xp, xp_to_next_level(experience.level));
+    }
+    let statuses = gs.ecs.read_storage::<StatusEffects>();
+    for (_player, statuses) in (&players, &statuses).join() {
+        for effect in statuses.effects.iter() {
+            println!("Status: {} ({} turns)", effect.kind.name(), effect.turns);
+        }
+    }
+    let clocks = gs.ecs.read_storage::<HungerClock>();
+    for (_player, clock) in (&players, &clocks).join() {
+        println!("Hunger: {} ({} turns)", clock.state.name(), clock.turns);
+    }
+    println!("Monsters: {}", monsters.join().count());
+    println!("Items carried: {}", backpack_items(&gs.ecs).len());
+    let items = gs.ecs.read_storage::<Item>();
+    let equipped = gs.ecs.read_storage::<Equipped>();
+    for item in equipped_items(&gs.ecs) {
+        println!("Equipped: {} ({})", items.get(item).unwrap().name, equipped.get(item).unwrap().slot.name());
+    }
+}
+
+/// Entry point for --headless, the script is either a file containing commands or the commands themselves
+pub fn main(mut gs: State, script: &str) {
+    let script = std::fs::read_to_string(script).unwrap_or_else(|_| script.to_string());
+    match parse_script(&script) {
+        Ok(actions) => {
+            let turns = run(&mut gs, &actions);
+            report(&gs, turns);
+        }
+        Err(message) => eprintln!("Invalid script: {}", message)
+    }
+}
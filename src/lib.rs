@@ -0,0 +1,446 @@
+// lib.rs
+//
+// The game itself, shared by the NQAR binary and the tests.
+use std::collections::HashSet;
+
+use rltk::{GameState, Point, RandomNumberGenerator, Rltk};
+use specs::prelude::*;
+use specs::saveload::SimpleMarkerAllocator;
+
+mod ai;
+pub use ai::{FlowMaps, MonsterAI};
+pub mod benchmark;
+mod camera;
+mod components;
+pub use components::*;
+mod experience;
+pub use experience::*;
+mod gamelog;
+pub use gamelog::{GameLog, RunStats};
+pub mod gui;
+pub mod headless;
+mod map;
+pub use map::*;
+mod player;
+pub use player::*;
+mod random_table;
+pub mod raws;
+pub mod saveload;
+mod spawner;
+mod systems;
+pub use systems::VisibilitySystem;
+pub use systems::MapIndexingSystem;
+pub use systems::MeleeCombatSystem;
+pub use systems::DamageSystem;
+pub use systems::{ItemCollectionSystem, ItemDropSystem, ItemRemoveSystem, ItemUseSystem};
+pub use systems::StatusEffectSystem;
+pub use systems::HungerSystem;
+pub use systems::InitiativeSystem;
+
+/// The current state of the world
+#[derive(PartialEq, Copy, Clone)]
+pub enum RunState {
+    Paused,
+    Running,
+    ShowInventory,
+    ShowDropItem,
+    ShowRemoveItem,
+    ShowTargeting { slot: usize, range: i32, radius: i32, cursor: Point },
+    NextLevel,
+    SaveGame,
+    LevelUp { selection: usize },
+    MainMenu { selection: gui::MainMenuSelection },
+    GameOver
+}
+
+/// Structure for the state of the game world
+pub struct State {
+    pub ecs: World,
+    pub run_state: RunState,
+    pub seed: u64,
+    pub builder: Option<BuilderType>,
+    pub corridors: Option<CorridorStyle>,
+    pub map_size: (i32, i32),
+    pub load_error: Option<String>
+}
+
+impl GameState for State {
+    fn tick(&mut self, ctx : &mut Rltk) {
+        ctx.cls();
+
+        // Draw the world first so that any menus end up on top of it, the full screen menus replace it
+        if !matches!(self.run_state, RunState::MainMenu { .. } | RunState::GameOver) {
+            camera::render_camera(&self.ecs, ctx);
+            gui::draw_ui(&self.ecs, ctx);
+            gui::draw_tooltips(&self.ecs, ctx);
+        }
+
+        match self.run_state {
+            RunState::Running => {
+                self.run_until_player_turn();
+                self.run_state = if !self.player_alive() {
+                    RunState::GameOver
+                } else if perks_pending(&self.ecs) > 0 {
+                    RunState::LevelUp { selection: 0 }
+                } else {
+                    RunState::Paused
+                }
+            }
+            RunState::Paused => {
+                self.run_state = player_input(self, ctx);
+            }
+            RunState::ShowInventory => {
+                let inventory = backpack_items(&self.ecs);
+                let (result, selection) = gui::show_inventory(&self.ecs, ctx, "Inventory", &inventory);
+                match result {
+                    gui::ItemMenuResult::Cancel => self.run_state = RunState::Paused,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => self.run_state = player_action(self, Action::UseItem(selection.unwrap()))
+                }
+            }
+            RunState::ShowDropItem => {
+                let inventory = backpack_items(&self.ecs);
+                let (result, selection) = gui::show_inventory(&self.ecs, ctx, "Drop Which Item?", &inventory);
+                match result {
+                    gui::ItemMenuResult::Cancel => self.run_state = RunState::Paused,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => self.run_state = player_action(self, Action::DropItem(selection.unwrap()))
+                }
+            }
+            RunState::ShowRemoveItem => {
+                let equipment = equipped_items(&self.ecs);
+                let (result, selection) = gui::show_inventory(&self.ecs, ctx, "Remove Which Item?", &equipment);
+                match result {
+                    gui::ItemMenuResult::Cancel => self.run_state = RunState::Paused,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => self.run_state = player_action(self, Action::RemoveItem(selection.unwrap()))
+                }
+            }
+            RunState::ShowTargeting { slot, range, radius, cursor } => {
+                let (result, cursor) = gui::ranged_target(&self.ecs, ctx, range, radius, cursor);
+                match result {
+                    gui::ItemMenuResult::Cancel => self.run_state = RunState::Paused,
+                    gui::ItemMenuResult::NoResponse => self.run_state = RunState::ShowTargeting { slot, range, radius, cursor },
+                    gui::ItemMenuResult::Selected => self.run_state = player_action(self, Action::UseItemAt(slot, cursor))
+                }
+            }
+            RunState::LevelUp { selection } => {
+                match gui::level_up(&self.ecs, ctx, selection) {
+                    gui::LevelUpResult::NoSelection { selected } =>
+                        self.run_state = RunState::LevelUp { selection: selected },
+                    gui::LevelUpResult::Selected { selected } => {
+                        // Levelling up more than once in a turn leaves more perks to pick
+                        apply_perk(&mut self.ecs, selected);
+                        if perks_pending(&self.ecs) == 0 {
+                            self.run_state = RunState::Paused;
+                        }
+                    }
+                }
+            }
+            RunState::NextLevel => {
+                self.goto_next_level();
+                self.run_state = RunState::Running
+            }
+            RunState::SaveGame => {
+                match saveload::save_game(&mut self.ecs) {
+                    Ok(()) => ctx.quit(),
+                    Err(error) => eprintln!("Unable to save the game, {}", error)
+                }
+                self.run_state = RunState::Paused
+            }
+            RunState::MainMenu { selection } => {
+                // A save that failed to load isn't offered again, a new game replaces it when it is saved
+                let can_continue = saveload::does_save_exist() && self.load_error.is_none();
+                match gui::main_menu(ctx, selection, can_continue, self.load_error.as_deref()) {
+                    gui::MainMenuResult::NoSelection { selected } =>
+                        self.run_state = RunState::MainMenu { selection: selected },
+                    gui::MainMenuResult::Selected { selected } => match selected {
+                        gui::MainMenuSelection::NewGame => {
+                            // Always start from a fresh world, whatever a failed load or the last run left behind
+                            self.new_game(self.seed);
+                            self.load_error = None;
+                            self.run_state = RunState::Running
+                        }
+                        gui::MainMenuSelection::Continue => self.continue_game(),
+                        gui::MainMenuSelection::Quit => ctx.quit()
+                    }
+                }
+            }
+            RunState::GameOver => {
+                // Pick the seed for the next game, the world is built when the player picks new game
+                if gui::game_over(&self.ecs, ctx, self.seed) == gui::GameOverResult::Dismissed {
+                    self.seed = self.ecs.write_resource::<RandomNumberGenerator>().next_u64();
+                    self.run_state = RunState::MainMenu { selection: gui::MainMenuSelection::NewGame };
+                }
+            }
+        }
+    }
+}
+
+impl State {
+    /// Create a new game world, the same seed, builder, corridors, and map size always produce the same world
+    pub fn new(seed: u64, builder: Option<BuilderType>, corridors: Option<CorridorStyle>, map_size: (i32, i32),
+               raws: raws::RawMaster) -> State {
+        // Prepare the components for the game
+        let mut gs = State {
+            ecs: World::new(),
+            run_state: RunState::Running,
+            seed,
+            builder,
+            corridors,
+            map_size,
+            load_error: None
+        };
+        register_components(&mut gs.ecs);
+
+        // Register the allocator for save markers, and the definitions of what can be spawned
+        gs.ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+        gs.ecs.insert(raws);
+
+        gs.new_game(seed);
+        gs
+    }
+
+    /// Replace the world with a new one, built from the seed given
+    pub fn new_game(&mut self, seed: u64) {
+        self.ecs.delete_all();
+
+        // Register the RNG so all rolls share the same stream, and reset what is kept for the run
+        self.ecs.insert(RandomNumberGenerator::seeded(seed));
+        self.ecs.insert(FlowMaps::default());
+        self.ecs.insert(RunStats::default());
+
+        // Register the log of messages for the player
+        let mut log = GameLog::default();
+        log.add("Welcome to NQAR, which is not quite a roguelike.");
+        self.ecs.insert(log);
+
+        // Create the player entity, the map will place them once it is built
+        let player_entity = spawner::player(&mut self.ecs, 0, 0);
+        self.ecs.insert(Point::new(0, 0));
+        self.ecs.insert(player_entity);
+
+        // Create the first level of the dungeon
+        self.generate_world_map(1);
+    }
+
+    /// Pick up where the last run left off, the save is removed since death is permanent
+    fn continue_game(&mut self) {
+        match saveload::load_game(&mut self.ecs) {
+            Ok(()) => {
+                self.ecs.write_resource::<GameLog>().add("Welcome back, the seed only applies to new levels.");
+
+                // The game can carry on even if the save can't be removed, the player just has to be told
+                if let Err(error) = saveload::delete_save() {
+                    eprintln!("Unable to delete the save file, {}", error);
+                    self.ecs.write_resource::<GameLog>()
+                        .add("The save file could not be removed, so this run can be continued again.");
+                }
+                self.run_state = RunState::Running;
+            }
+            Err(error) => {
+                eprintln!("Unable to load the game, {}", error);
+                self.load_error = Some(error.to_string());
+            }
+        }
+    }
+
+    /// Returns true if the player is still alive
+    pub fn player_alive(&self) -> bool {
+        let player_entity = *self.ecs.fetch::<Entity>();
+        self.ecs.read_storage::<CombatStats>().get(player_entity).is_some_and(|stats| stats.hp > 0)
+    }
+
+    /// Build a new map for the given depth, populate it, and place the player at the start
+    fn generate_world_map(&mut self, depth: i32) {
+        // Create the map
+        let map_builder = {
+            let mut rng = self.ecs.write_resource::<RandomNumberGenerator>();
+            let builder_type = self.builder.unwrap_or_else(|| BuilderType::random(&mut rng));
+            let corridors = self.corridors.unwrap_or_else(|| CorridorStyle::random(&mut rng));
+            let mut map_builder = new_builder(builder_type, depth, self.map_size.0, self.map_size.1, corridors);
+            map_builder.build_map(&mut rng);
+            map_builder
+        };
+
+        // Make sure everything can be reached from the start, keeping the statistics to report how the map turned out
+        let mut map = map_builder.get_map();
+        let start = map_builder.get_starting_position();
+        let stats = validate_map(&mut map, start);
+
+        // Hang the doors, then add some monsters and items to the map, keeping them off the start, doors, and each other
+        let mut used = HashSet::from([map.xy_idx(start.x, start.y)]);
+        let doors = map_builder.get_doors().into_iter()
+            .filter(|door| map.tiles[map.xy_idx(door.x, door.y)] == TileType::Floor);
+        for door in doors {
+            spawner::door(&mut self.ecs, door.x, door.y);
+            used.insert(map.xy_idx(door.x, door.y));
+        }
+        let mut number = 0;
+        for region in map_builder.get_spawn_regions().iter() {
+            spawner::spawn_region(&mut self.ecs, &map, region, depth, &mut used, &mut number);
+        }
+
+        // Move the player to the start, and let everyone know where they are
+        let player_entity = *self.ecs.fetch::<Entity>();
+        if let Some(pos) = self.ecs.write_storage::<Position>().get_mut(player_entity) {
+            pos.x = start.x;
+            pos.y = start.y;
+        }
+        if let Some(viewshed) = self.ecs.write_storage::<Viewshed>().get_mut(player_entity) {
+            viewshed.dirty = true;
+        }
+        self.ecs.insert(start);
+
+        // Register the map, and index it so the doors block the view from the start
+        self.ecs.insert(map);
+        self.ecs.insert(stats);
+        let mut indexing = MapIndexingSystem{};
+        indexing.run_now(&self.ecs);
+    }
+
+    /// Return the entities that should be removed when the player leaves the level, they keep what they carry and wear
+    fn entities_to_remove(&self) -> Vec<Entity> {
+        let entities = self.ecs.entities();
+        let backpack = self.ecs.read_storage::<InBackpack>();
+        let equipped = self.ecs.read_storage::<Equipped>();
+        let player_entity = *self.ecs.fetch::<Entity>();
+        entities.join()
+            .filter(|entity| *entity != player_entity)
+            .filter(|entity| backpack.get(*entity).is_none_or(|pack| pack.owner != player_entity))
+            .filter(|entity| equipped.get(*entity).is_none_or(|worn| worn.owner != player_entity))
+            .collect()
+    }
+
+    /// Take the player down the stairs to a fresh level
+    fn goto_next_level(&mut self) {
+        // Clear out everything that isn't coming along
+        for target in self.entities_to_remove() {
+            self.ecs.delete_entity(target).expect("Unable to delete entity");
+        }
+
+        // Build the next level down
+        let depth = self.ecs.fetch::<Map>().depth + 1;
+        self.generate_world_map(depth);
+        self.ecs.write_resource::<GameLog>().add(format!("You descend to depth {}.", depth));
+    }
+
+    /// Run the systems until it is the player's turn again, the player sleeps through any turns they are asleep for
+    pub fn run_until_player_turn(&mut self) {
+        loop {
+            self.run_systems();
+            if !self.player_alive() { break; }
+
+            // Stop rather than wait forever if nothing is able to act
+            if self.ecs.read_storage::<MyTurn>().is_empty() { break; }
+            let player_entity = *self.ecs.fetch::<Entity>();
+            if self.ecs.read_storage::<MyTurn>().get(player_entity).is_none() { continue; }
+            let asleep = self.ecs.read_storage::<StatusEffects>().get(player_entity)
+                .is_some_and(|statuses| statuses.has(StatusKind::Asleep));
+            if !asleep { break; }
+            self.ecs.write_resource::<GameLog>().add("You are asleep.");
+        }
+    }
+
+    fn run_systems(&mut self) {
+        // Visibility
+        let mut visibility = VisibilitySystem{};
+        visibility.run_now(&self.ecs);
+
+        // Monster AI
+        let mut mob = MonsterAI{};
+        mob.run_now(&self.ecs);        
+
+        // Status effects wear off after the monsters have had their turn, and everyone gets a little hungrier
+        let mut status = StatusEffectSystem{};
+        status.run_now(&self.ecs);
+        let mut hunger = HungerSystem{};
+        hunger.run_now(&self.ecs);
+
+        // Indexing of the map - marking occupied tiles
+        let mut indexing = MapIndexingSystem{};
+        indexing.run_now(&self.ecs);
+
+        // Inventory management, anything hurt by an item takes the damage before it gets to attack
+        let mut pickup = ItemCollectionSystem{};
+        pickup.run_now(&self.ecs);
+        let mut use_items = ItemUseSystem{};
+        use_items.run_now(&self.ecs);
+        let mut remove_items = ItemRemoveSystem{};
+        remove_items.run_now(&self.ecs);
+        let mut drop_items = ItemDropSystem{};
+        drop_items.run_now(&self.ecs);
+        let mut damage = DamageSystem{};
+        damage.run_now(&self.ecs);
+
+        // Combat resolution
+        let mut melee = MeleeCombatSystem{};
+        melee.run_now(&self.ecs);
+        damage.run_now(&self.ecs);
+
+        // Maintain step
+        self.ecs.maintain();
+
+        // Clean-up anything that died this turn
+        systems::delete_the_dead(&mut self.ecs);
+
+        // Work out who acts next, anything logged from here on happened on the player's next turn
+        let mut initiative = InitiativeSystem{};
+        initiative.run_now(&self.ecs);
+        let player_entity = *self.ecs.fetch::<Entity>();
+        if self.ecs.read_storage::<MyTurn>().get(player_entity).is_some() {
+            self.ecs.write_resource::<GameLog>().turn += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+impl State {
+    /// Return a new game from the seed given, using the default builders, map size, and raw files
+    pub fn for_tests(seed: u64) -> State {
+        State::new(seed, None, None, (MAP_WIDTH, MAP_HEIGHT), raws::load().expect("Unable to load the raw files"))
+    }
+
+    /// Delete all but the given number of monsters, and return those left in the order they act
+    pub fn keep_monsters(&mut self, count: usize) -> Vec<Entity> {
+        let mut monsters: Vec<Entity> = (&self.ecs.entities(), &self.ecs.read_storage::<Monster>()).join()
+            .map(|(entity, _monster)| entity)
+            .collect();
+        let others = monsters.split_off(count);
+        self.ecs.delete_entities(&others).expect("Unable to delete the monsters");
+        MapIndexingSystem{}.run_now(&self.ecs);
+        monsters
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn descending_keeps_only_the_player_and_their_belongings() {
+        let mut gs = State::for_tests(1234);
+        let player = *gs.ecs.fetch::<Entity>();
+        let carried = gs.ecs.create_entity()
+            .with(Item{ name: "Ration".to_string() })
+            .with(InBackpack{ owner: player })
+            .build();
+        let worn = gs.ecs.create_entity()
+            .with(Item{ name: "Dagger".to_string() })
+            .with(Equipped{ owner: player, slot: EquipmentSlot::Weapon })
+            .build();
+        let left_behind = gs.entities_to_remove();
+        assert!(!left_behind.is_empty());
+
+        gs.goto_next_level();
+        assert_eq!(gs.ecs.fetch::<Map>().depth, 2);
+        let entities = gs.ecs.entities();
+        assert!(entities.is_alive(player) && entities.is_alive(carried) && entities.is_alive(worn));
+        assert!(left_behind.iter().all(|entity| !entities.is_alive(*entity)));
+
+        // The player is moved to the start of the new level
+        let start = *gs.ecs.fetch::<Point>();
+        let pos = gs.ecs.read_storage::<Position>().get(player).cloned().unwrap();
+        assert_eq!((pos.x, pos.y), (start.x, start.y));
+    }
+}
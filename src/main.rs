@@ -1,150 +1,97 @@
 // main.rs
 //
 // Main entry point for NQAR.
-use rltk::{GameState, Point, Rltk, RGB};
-use specs::prelude::*;
+use rltk::RandomNumberGenerator;
 
-mod ai;
-pub use ai::MonsterAI;
-mod components;
-pub use components::*;
-mod map;
-pub use map::*;
-mod player;
-pub use player::*;
-mod systems;
-pub use systems::VisibilitySystem;
-pub use systems::MapIndexingSystem;
+use nqar::{benchmark, gui, headless, raws, saveload, BuilderType, CorridorStyle, RunState, State, MAP_HEIGHT, MAP_WIDTH,
+           MIN_MAP_SIZE};
 
-/// The current state of the world
-#[derive(PartialEq, Copy, Clone)]
-pub enum RunState { Paused, Running }
-
-/// Structure for the state of the game world
-pub struct State {
-    pub ecs: World,
-    pub run_state: RunState
+/// Return the value that follows the command line flag given, if the flag was supplied
+fn arg_value(flag: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    let ndx = args.iter().position(|arg| arg == flag)?;
+    args.get(ndx + 1).cloned()
 }
 
-impl GameState for State {
-    fn tick(&mut self, ctx : &mut Rltk) {
-        ctx.cls();
-
-        if self.run_state == RunState::Running {
-            self.run_systems();
-            self.run_state = RunState::Paused
-        } else {
-            self.run_state = player_input(self, ctx);
-        }
-
-        draw_map(&self.ecs, ctx);
-        
-        let positions = self.ecs.read_storage::<Position>();
-        let renderables = self.ecs.read_storage::<Renderable>();
-        let map = self.ecs.fetch::<Map>();
-
-        for (pos, render) in (&positions, &renderables).join() {
-            // Only render what the player can actually see
-            let idx = map.xy_idx(pos.x, pos.y);
-            if map.visible_tiles[idx] {
-                ctx.set(pos.x, pos.y, render.fg, render.bg, render.glyph);
-            }
+/// Return the seed supplied via --seed, or a new random seed if one was not given
+fn parse_seed() -> u64 {
+    if let Some(value) = arg_value("--seed") {
+        if let Ok(seed) = value.parse::<u64>() {
+            return seed;
         }
+        eprintln!("--seed expects an unsigned integer, using a random seed instead");
     }
+    RandomNumberGenerator::new().next_u64()
 }
 
-impl State {
-    fn run_systems(&mut self) {
-        // Visibility
-        let mut visibility = VisibilitySystem{};
-        visibility.run_now(&self.ecs);
-
-        // Monster AI
-        let mut mob = MonsterAI{};
-        mob.run_now(&self.ecs);        
+/// Return the map builder supplied via --builder, if one was given
+fn parse_builder() -> Option<BuilderType> {
+    let value = arg_value("--builder")?;
+    let builder = BuilderType::from_name(&value);
+    if builder.is_none() {
+        eprintln!("Unknown --builder '{}', expected rooms, bsp, caves, or drunkard", value);
+    }
+    builder
+}
 
-        // Indexing of the map - marking occupied tiles
-        let mut indexing = MapIndexingSystem{};
-        indexing.run_now(&self.ecs);
+/// Return the corridor style supplied via --corridors, if one was given
+fn parse_corridors() -> Option<CorridorStyle> {
+    let value = arg_value("--corridors")?;
+    let corridors = CorridorStyle::from_name(&value);
+    if corridors.is_none() {
+        eprintln!("Unknown --corridors '{}', expected sequential, mst, or nearest, with an optional -diagonal", value);
+    }
+    corridors
+}
 
-        // Maintain step
-        self.ecs.maintain();
+/// Return the map size supplied via --size as WIDTHxHEIGHT, or the default size if one was not given
+fn parse_map_size() -> (i32, i32) {
+    let default = (MAP_WIDTH, MAP_HEIGHT);
+    let Some(value) = arg_value("--size") else { return default };
+    let size = value.split_once('x').and_then(|(width, height)| Some((width.parse::<i32>().ok()?, height.parse::<i32>().ok()?)));
+    match size {
+        Some((width, height)) if width >= MIN_MAP_SIZE && height >= MIN_MAP_SIZE => (width, height),
+        _ => {
+            eprintln!("--size expects WIDTHxHEIGHT, each at least {}, using {}x{} instead", MIN_MAP_SIZE, default.0, default.1);
+            default
+        }
     }
 }
 
 fn main() -> rltk::BError {
+    // Prepare the world, the seed is reported so that a dungeon can be reproduced
+    let seed = parse_seed();
+    println!("Seed: {}", seed);
+    let raws = match raws::load() {
+        Ok(raws) => raws,
+        Err(errors) => {
+            for error in errors.iter() { eprintln!("{}", error); }
+            return Err(format!("{} problem(s) found in the raw files", errors.len()).into());
+        }
+    };
+    let mut gs = State::new(seed, parse_builder(), parse_corridors(), parse_map_size(), raws);
+
+    // Scripted runs don't need a window, or the save file
+    if let Some(script) = arg_value("--headless") {
+        headless::main(gs, &script);
+        return Ok(());
+    }
+    if let Some(rounds) = arg_value("--benchmark") {
+        benchmark::main(gs, &rounds);
+        return Ok(());
+    }
+
+    // Start at the main menu, ready to continue if there is a save
+    use gui::MainMenuSelection;
+    let selection = if saveload::does_save_exist() { MainMenuSelection::Continue } else { MainMenuSelection::NewGame };
+    gs.run_state = RunState::MainMenu { selection };
+
     // Prepare the game window
     use rltk::RltkBuilder;
     let context = RltkBuilder::simple80x50()
         .with_title("NQAR")
         .build()?;
 
-    // Prepare the components for the game
-    let mut gs = State {
-        ecs: World::new(),
-        run_state: RunState::Running
-    };
-    gs.ecs.register::<Monster>();
-    gs.ecs.register::<OccupiesTile>();
-    gs.ecs.register::<Position>();
-    gs.ecs.register::<Renderable>();
-    gs.ecs.register::<Player>();
-    gs.ecs.register::<Viewshed>();
-
-    // Create the map
-    let map: Map = Map::new_map();
-    
-    // Create the RNG
-    let mut rng = rltk::RandomNumberGenerator::new();
-
-    // Now add some basic monsters to the map, note that we skip the first room so the player can spawn there
-    for (count, room) in map.rooms.iter().skip(1).enumerate() {
-        // Determine the monster type
-        let glyph : rltk::FontCharType;
-        let name : String;
-        let roll = rng.roll_dice(1, 2);
-        match roll {
-            1 => { glyph = rltk::to_cp437('g'); name = "Goblin".to_string(); }
-            _ => { glyph = rltk::to_cp437('o'); name = "Orc".to_string(); }
-        }        
-
-        // Add the monster to the center of the room
-        let (x, y) = room.center();
-        gs.ecs.create_entity()
-            .with(Position{ x, y })
-            .with(Renderable{
-                glyph: glyph,
-                fg: RGB::named(rltk::GREEN),
-                bg: RGB::named(rltk::BLACK),
-            })
-            .with(Monster{
-                name: format!("{} #{}", &name, count),
-            })
-            .with(Viewshed{ visible_tiles : Vec::new(), range: DEFAULT_FOV, dirty: true })
-            .with(OccupiesTile{})
-            .build();
-    }    
-    
-    // Create the player entity
-    let (player_x, player_y) = map.rooms[0].center();
-    gs.ecs
-        .create_entity()
-        .with(Position { x: player_x, y: player_y })
-        .with(Renderable {
-            glyph: rltk::to_cp437('@'),
-            fg: RGB::named(rltk::YELLOW),
-            bg: RGB::named(rltk::BLACK),
-        })
-        .with(Player{})
-        .with(Viewshed { visible_tiles: Vec::new(), range: DEFAULT_FOV, dirty: true })
-        .build();
-
-    // Register the player location
-    gs.ecs.insert(Point::new(player_x, player_y));
-
-    // Register the map
-    gs.ecs.insert(map);
-
     // Run the main loop of th game
     rltk::main_loop(context, gs)
 }
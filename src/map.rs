@@ -3,88 +3,77 @@
 // Defines the map for NQAR.
 use std::cmp::{min, max};
 
-use rltk::{Algorithm2D, BaseMap, Point, RandomNumberGenerator, Rltk, RGB};
+use rltk::{Algorithm2D, BaseMap, FontCharType, LineAlg, NavigationPath, Point, RGB};
+use serde::{Deserialize, Serialize};
 use specs::prelude::*;
 
+use crate::{BlocksVisibility, Door, OccupiesTile, Renderable};
+
+mod builders;
+pub use builders::*;
 mod rectangle;
 pub use rectangle::*;
 
-// Constants for the screen bounds
+// Constants for the screen bounds of the map viewport, the rows below it are used by the UI panel
 pub const WINDOW_WIDTH: i32 = 79;
-pub const WINDOW_HEIGHT: i32 = 49;
+pub const WINDOW_HEIGHT: i32 = 43;
+
+// Constants for the default size of the map, which is independent of the viewport
+pub const MAP_WIDTH: i32 = 120;
+pub const MAP_HEIGHT: i32 = 80;
+
+/// The smallest width or height a map can have and still fit the rooms the builders make
+pub const MIN_MAP_SIZE: i32 = 30;
 
-#[derive(PartialEq, Copy, Clone)]
+#[derive(PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub enum TileType {
-    Floor, Wall
+    Floor, Wall, DownStairs
+}
+
+impl TileType {
+    /// Return the name of the tile, as shown to the player
+    pub fn name(&self) -> &'static str {
+        match self {
+            TileType::Floor => "Floor",
+            TileType::Wall => "Wall",
+            TileType::DownStairs => "Stairs down",
+        }
+    }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Map {
     pub tiles: Vec<TileType>,
     pub occupied_tiles: Vec<bool>,
     pub revealed_tiles: Vec<bool>,
     pub visible_tiles: Vec<bool>,
+    pub view_blocked: Vec<bool>,
+    pub closed_doors: Vec<bool>,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub tile_content: Vec<Vec<Entity>>,
     pub rooms: Vec<Rectangle>,
     pub width: i32,
     pub height: i32,
+    pub depth: i32,
 }
 
 impl Map {
-    /// Generate a new map
-    pub fn new_map() -> Map {
-        // Allocate the memory for the map and rooms
-        let length = (WINDOW_HEIGHT * WINDOW_WIDTH) as usize;
-        let mut map = Map {
+    /// Allocate a new map that is solid wall, ready for a builder to carve out
+    fn new(depth: i32, width: i32, height: i32) -> Map {
+        let length = (width * height) as usize;
+        Map {
             tiles: vec![TileType::Wall; length],
             occupied_tiles: vec![false; length],
             revealed_tiles: vec![false; length],
             visible_tiles: vec![false; length],
+            view_blocked: vec![false; length],
+            closed_doors: vec![false; length],
+            tile_content: vec![Vec::new(); length],
             rooms: Vec::new(),
-            width: WINDOW_WIDTH,
-            height: WINDOW_HEIGHT            
-        };
-
-        // TODO Placeholder constants for the rooms
-        const MAX_ROOMS: i32 = 30;
-        const MIN_SIZE: i32 = 6;
-        const MAX_SIZE: i32 = 10;
-
-        // Prepare the RNG
-        let mut rng = RandomNumberGenerator::new();
-
-        for _idx in 0..MAX_ROOMS {
-            // Create a new room
-            let width = rng.range(MIN_SIZE, MAX_SIZE);
-            let height = rng.range(MIN_SIZE, MAX_SIZE);
-            let x = rng.roll_dice(1, WINDOW_WIDTH - width - 1) - 1;
-            let y = rng.roll_dice(1, WINDOW_HEIGHT - height - 1) - 1;
-            let new_room = Rectangle::new(x, y, width, height);
-
-            // Check to see if the room can be placed
-            for other_room in map.rooms.iter() {
-                if new_room.intersect(other_room) {
-                    // Nope, try again
-                    continue;
-                }
-            }
-
-            // The location is valid, add the room and the corridors
-            map.apply_room_to_map(&new_room);
-            if !map.rooms.is_empty() {
-                let (new_x, new_y) = new_room.center();
-                let (prev_x, prev_y) = map.rooms[map.rooms.len() - 1].center();
-                if rng.range(0,2) == 1 {
-                    map.apply_horizontal_tunnel(prev_x, new_x, prev_y);
-                    map.apply_vertical_tunnel(prev_y, new_y, new_x);
-                } else {
-                    map.apply_vertical_tunnel(prev_y, new_y, prev_x);
-                    map.apply_horizontal_tunnel(prev_x, new_x, new_y);
-                }
-            }
-            map.rooms.push(new_room);
+            width,
+            height,
+            depth
         }
-
-        // Return the map
-        map
     }
 
     fn apply_room_to_map(&mut self, room: &Rectangle) {
@@ -100,8 +89,8 @@ impl Map {
     fn apply_horizontal_tunnel(&mut self, upper_x: i32, lower_x: i32, y: i32) {
         for x in min(upper_x, lower_x) ..= max(upper_x, lower_x) {
             let idx = self.xy_idx(x, y);
-            if idx > 0 && idx < (WINDOW_HEIGHT * WINDOW_WIDTH) as usize {
-                self.tiles[idx as usize] = TileType::Floor;
+            if idx > 0 && idx < self.tiles.len() {
+                self.tiles[idx] = TileType::Floor;
             }
         }
     }
@@ -109,8 +98,8 @@ impl Map {
     fn apply_vertical_tunnel(&mut self, upper_y: i32, lower_y: i32, x:i32) {
         for y in min(upper_y, lower_y) ..= max(upper_y, lower_y) {
             let idx = self.xy_idx(x, y);
-            if idx > 0 && idx < (WINDOW_HEIGHT * WINDOW_WIDTH) as usize {
-                self.tiles[idx as usize] = TileType::Floor;
+            if idx > 0 && idx < self.tiles.len() {
+                self.tiles[idx] = TileType::Floor;
             }
         }
     }
@@ -122,17 +111,112 @@ impl Map {
         }
     }
 
-    fn is_exit_valid(&self, x:i32, y:i32) -> bool {
+    /// Clear the layer of tiles that have their view blocked by an entity
+    pub fn clear_view_blocked(&mut self) {
+        for blocked in self.view_blocked.iter_mut() {
+            *blocked = false;
+        }
+    }
+
+    /// Clear the layer of tiles that have a closed door on them
+    pub fn clear_closed_doors(&mut self) {
+        for closed in self.closed_doors.iter_mut() {
+            *closed = false;
+        }
+    }
+
+    /// Clear the index of entities on each tile
+    pub fn clear_content_index(&mut self) {
+        for content in self.tile_content.iter_mut() {
+            content.clear();
+        }
+    }
+
+    /// Open the closed door on the tile, if there is one, returning whether there was. The map is updated right away so
+    /// the doorway can be seen through and moved in to before the map is next indexed
+    pub fn open_door(&mut self, idx: usize, doors: &mut WriteStorage<Door>, occupiers: &mut WriteStorage<OccupiesTile>,
+                     blockers: &mut WriteStorage<BlocksVisibility>, renderables: &mut WriteStorage<Renderable>)
+                     -> bool {
+        let closed_door = self.tile_content[idx].iter().copied()
+            .find(|entity| doors.get(*entity).is_some_and(|door| !door.open));
+        let Some(door) = closed_door else { return false };
+        doors.get_mut(door).unwrap().open = true;
+        occupiers.remove(door);
+        blockers.remove(door);
+        if let Some(render) = renderables.get_mut(door) {
+            render.glyph = rltk::to_cp437('\'');
+        }
+        self.occupied_tiles[idx] = false;
+        self.view_blocked[idx] = false;
+        self.closed_doors[idx] = false;
+        true
+    }
+
+    fn is_exit_valid(&self, x:i32, y:i32, goal: Option<usize>) -> bool {
         if x < 1 || x > self.width-1 || y < 1 || y > self.height-1 { 
             return false; 
         }
         let idx = self.xy_idx(x, y);
-        !self.occupied_tiles[idx]
+        Some(idx) == goal || !self.occupied_tiles[idx] || self.closed_doors[idx]
+    }
+
+    /// Return the exits from the given location, the goal is always a valid exit whatever occupies it, and closed doors
+    /// are too since monsters can open them
+    fn exits(&self, idx: usize, goal: Option<usize>) -> rltk::SmallVec<[(usize, f32); 10]> {
+        let mut exits = rltk::SmallVec::new();
+        let x = idx as i32 % self.width;
+        let y = idx as i32 / self.width;
+        let width = self.width as usize;
+    
+        // Cardinal directions
+        if self.is_exit_valid(x - 1, y, goal) { exits.push((idx - 1, 1.0)) };
+        if self.is_exit_valid(x + 1, y, goal) { exits.push((idx + 1, 1.0)) };
+        if self.is_exit_valid(x, y - 1, goal) { exits.push((idx - width, 1.0)) };
+        if self.is_exit_valid(x, y + 1, goal) { exits.push((idx + width, 1.0)) };
+
+        // Diagonals
+        if self.is_exit_valid(x - 1, y - 1, goal) { exits.push(((idx - width) - 1, 1.45)); }
+        if self.is_exit_valid(x + 1, y - 1, goal) { exits.push(((idx - width) + 1, 1.45)); }
+        if self.is_exit_valid(x - 1, y + 1, goal) { exits.push(((idx + width) - 1, 1.45)); }
+        if self.is_exit_valid(x + 1, y + 1, goal) { exits.push(((idx + width) + 1, 1.45)); }        
+    
+        exits
+    }
+
+    /// Return the path from one point to another, the goal can be reached even when something occupies it so that
+    /// paths lead right up to whoever is standing there
+    pub fn find_path(&self, from: Point, to: Point) -> NavigationPath {
+        let goal = self.xy_idx(to.x, to.y);
+        rltk::a_star_search(self.xy_idx(from.x, from.y), goal, &PathTo { map: self, goal })
+    }
+
+    /// Returns true if the X, Y coordinates are on the map
+    pub fn in_bounds(&self, x: i32, y: i32) -> bool {
+        x >= 0 && x < self.width && y >= 0 && y < self.height
+    }
+
+    /// Return where a projectile from the start towards the end lands, it stops short of anything opaque and on
+    /// the first thing that occupies a tile
+    pub fn line_of_fire(&self, start: Point, end: Point) -> Point {
+        let mut impact = start;
+        for point in rltk::line2d(LineAlg::Bresenham, start, end).into_iter().filter(|point| *point != start) {
+            if !self.in_bounds(point.x, point.y) { break; }
+            let idx = self.xy_idx(point.x, point.y);
+            if self.is_opaque(idx) { break; }
+            impact = point;
+            if self.occupied_tiles[idx] { break; }
+        }
+        impact
     }
 
     /// Convert from X, Y coordinates to index
     pub fn xy_idx(&self, x: i32, y: i32) -> usize{
-        ((y * WINDOW_WIDTH) + x) as usize
+        ((y * self.width) + x) as usize
+    }
+
+    /// Convert from an index to the point it is at
+    pub fn idx_point(&self, idx: usize) -> Point {
+        Point::new(idx as i32 % self.width, idx as i32 / self.width)
     }
 }
 
@@ -146,24 +230,7 @@ impl Algorithm2D for Map {
 impl BaseMap for Map {
     // Return the available exits from the given location
     fn get_available_exits(&self, idx:usize) -> rltk::SmallVec<[(usize, f32); 10]> {
-        let mut exits = rltk::SmallVec::new();
-        let x = idx as i32 % self.width;
-        let y = idx as i32 / self.width;
-        let width = self.width as usize;
-    
-        // Cardinal directions
-        if self.is_exit_valid(x - 1, y) { exits.push((idx - 1, 1.0)) };
-        if self.is_exit_valid(x + 1, y) { exits.push((idx + 1, 1.0)) };
-        if self.is_exit_valid(x, y - 1) { exits.push((idx - width, 1.0)) };
-        if self.is_exit_valid(x, y + 1) { exits.push((idx + width, 1.0)) };
-
-        // Diagonals
-        if self.is_exit_valid(x - 1, y - 1) { exits.push(((idx - width) - 1, 1.45)); }
-        if self.is_exit_valid(x + 1, y - 1) { exits.push(((idx - width) + 1, 1.45)); }
-        if self.is_exit_valid(x - 1, y + 1) { exits.push(((idx + width) - 1, 1.45)); }
-        if self.is_exit_valid(x + 1, y + 1) { exits.push(((idx + width) + 1, 1.45)); }        
-    
-        exits
+        self.exits(idx, None)
     }
 
     // Return the Pythagorean distance between two points
@@ -174,48 +241,75 @@ impl BaseMap for Map {
         rltk::DistanceAlg::Pythagoras.distance2d(one, two)
     }
 
-    // Return true if the tile is opaque
+    // Return true if the tile is opaque, either a wall or something blocking the view
     fn is_opaque(&self, idx: usize) -> bool {
-        self.tiles[idx] == TileType::Wall
+        self.tiles[idx] == TileType::Wall || self.view_blocked[idx]
     }
 }
 
-/// Draw the map to the screen
-pub fn draw_map(ecs: &World, ctx: &mut Rltk) {
-    // Get the map
-    let map = ecs.fetch::<Map>();
-    
-    // Draw the map to the screen
-    let mut x = 0;
-    let mut y = 0;
-    for (idx, tile) in map.tiles.iter().enumerate() {
-        // Only render what we have seen
-        if map.revealed_tiles[idx] {
-            
-            // Prepare the glyph and foreground color based upon the tile
-            let glyph;
-            let mut fg;
-            match tile {
-                TileType::Floor => {
-                    glyph = rltk::to_cp437('.');
-                    fg = RGB::from_f32(0.5, 0.5, 0.5);
-                }
-                TileType::Wall => {
-                    glyph = rltk::to_cp437('#');
-                    fg = RGB::from_f32(1.0, 0.0, 0.0);
-                }            
-            }
+/// The map as seen when pathing to a goal, which is passable whatever is standing on it
+struct PathTo<'a> {
+    map: &'a Map,
+    goal: usize,
+}
 
-            // Render the tile if it is visible
-            if !map.visible_tiles[idx] { fg = fg.to_greyscale() }
-            ctx.set(x, y, fg, RGB::from_f32(0.0, 0.0, 0.0), glyph);
-        }
+impl BaseMap for PathTo<'_> {
+    fn get_available_exits(&self, idx:usize) -> rltk::SmallVec<[(usize, f32); 10]> {
+        self.map.exits(idx, Some(self.goal))
+    }
+
+    fn get_pathing_distance(&self, idx1:usize, idx2:usize) -> f32 {
+        self.map.get_pathing_distance(idx1, idx2)
+    }
+}
+
+/// Return the glyph and colour used to draw the tile, greyed out if it is not currently visible
+pub fn tile_glyph(tile: TileType, visible: bool) -> (FontCharType, RGB) {
+    let (glyph, fg) = match tile {
+        TileType::Floor => (rltk::to_cp437('.'), RGB::from_f32(0.5, 0.5, 0.5)),
+        TileType::Wall => (rltk::to_cp437('#'), RGB::from_f32(1.0, 0.0, 0.0)),
+        TileType::DownStairs => (rltk::to_cp437('>'), RGB::from_f32(0.0, 1.0, 1.0)),
+    };
+    if visible { (glyph, fg) } else { (glyph, fg.to_greyscale()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        // Move to next coordinates
-        x += 1;
-        if x > WINDOW_WIDTH - 1 {
-            x = 0;
-            y += 1;
+    /// Return a map with an open floor surrounded by wall
+    fn open_map() -> Map {
+        let mut map = Map::new(1, 20, 10);
+        map.apply_room_to_map(&Rectangle::new(0, 0, 18, 8));
+        map.populate_occupied();
+        map
+    }
+
+    #[test]
+    fn line_of_fire_reaches_the_target_when_clear() {
+        let map = open_map();
+        assert_eq!(map.line_of_fire(Point::new(2, 5), Point::new(15, 5)), Point::new(15, 5));
+    }
+
+    #[test]
+    fn line_of_fire_stops_at_the_first_occupied_tile() {
+        let mut map = open_map();
+        for x in [8, 10] {
+            let idx = map.xy_idx(x, 5);
+            map.occupied_tiles[idx] = true;
         }
+        assert_eq!(map.line_of_fire(Point::new(2, 5), Point::new(15, 5)), Point::new(8, 5));
     }
-}  
\ No newline at end of file
+
+    #[test]
+    fn line_of_fire_stops_short_of_walls_and_closed_doors() {
+        let mut map = open_map();
+        let wall = map.xy_idx(8, 5);
+        map.tiles[wall] = TileType::Wall;
+        assert_eq!(map.line_of_fire(Point::new(2, 5), Point::new(15, 5)), Point::new(7, 5));
+
+        let door = map.xy_idx(5, 5);
+        map.view_blocked[door] = true;
+        assert_eq!(map.line_of_fire(Point::new(2, 5), Point::new(15, 5)), Point::new(4, 5));
+    }
+}
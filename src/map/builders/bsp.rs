@@ -0,0 +1,109 @@
+// bsp.rs
+//
+// Builder for maps generated by binary space partitioning, which gives evenly spread rooms.
+use rltk::{Point, RandomNumberGenerator};
+
+use super::{connect_rooms, place_stairs_in_last_room, room_door_locations, room_spawn_regions, CorridorStyle,
+            MapBuilder};
+use crate::map::{Map, Rectangle};
+
+// Constants for the partitioning
+const MAX_ATTEMPTS: i32 = 240;
+const MIN_SIZE: i32 = 3;
+
+pub struct BspBuilder {
+    map: Map,
+    rects: Vec<Rectangle>,
+    corridors: CorridorStyle,
+}
+
+impl BspBuilder {
+    pub(super) fn new(depth: i32, width: i32, height: i32, corridors: CorridorStyle) -> BspBuilder {
+        BspBuilder { map: Map::new(depth, width, height), rects: Vec::new(), corridors }
+    }
+
+    /// Split the rectangle into quarters, and add them to the list of candidates
+    fn add_subrects(&mut self, rect: Rectangle) {
+        let width = i32::abs(rect.upper_x - rect.lower_x);
+        let height = i32::abs(rect.upper_y - rect.lower_y);
+        let half_width = i32::max(width / 2, 1);
+        let half_height = i32::max(height / 2, 1);
+
+        self.rects.push(Rectangle::new(rect.upper_x, rect.upper_y, half_width, half_height));
+        self.rects.push(Rectangle::new(rect.upper_x, rect.upper_y + half_height, half_width, half_height));
+        self.rects.push(Rectangle::new(rect.upper_x + half_width, rect.upper_y, half_width, half_height));
+        self.rects.push(Rectangle::new(rect.upper_x + half_width, rect.upper_y + half_height, half_width, half_height));
+    }
+
+    /// Return a room that fits somewhere inside of the rectangle
+    fn random_room(&self, rect: Rectangle, rng: &mut RandomNumberGenerator) -> Rectangle {
+        let rect_width = i32::abs(rect.upper_x - rect.lower_x);
+        let rect_height = i32::abs(rect.upper_y - rect.lower_y);
+
+        let width = i32::max(MIN_SIZE, rng.roll_dice(1, i32::min(rect_width, 10)) - 1) + 1;
+        let height = i32::max(MIN_SIZE, rng.roll_dice(1, i32::min(rect_height, 10)) - 1) + 1;
+        let x = rect.upper_x + rng.roll_dice(1, 6) - 1;
+        let y = rect.upper_y + rng.roll_dice(1, 6) - 1;
+        Rectangle::new(x, y, width, height)
+    }
+
+    /// Returns true if the room is inside of the map, with a border, and doesn't touch any other rooms
+    fn is_possible(&self, room: &Rectangle) -> bool {
+        // Expand the room so that rooms keep some wall between them
+        let expanded = Rectangle {
+            upper_x: room.upper_x - 2,
+            upper_y: room.upper_y - 2,
+            lower_x: room.lower_x + 2,
+            lower_y: room.lower_y + 2
+        };
+        if expanded.upper_x < 1 || expanded.upper_y < 1 ||
+           expanded.lower_x > self.map.width - 2 || expanded.lower_y > self.map.height - 2 {
+            return false;
+        }
+        !self.map.rooms.iter().any(|other| other.intersect(&expanded))
+    }
+}
+
+impl MapBuilder for BspBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        // Start with a single rectangle covering the map, less a border
+        self.rects.clear();
+        let first = Rectangle::new(2, 2, self.map.width - 5, self.map.height - 5);
+        self.rects.push(first);
+        self.add_subrects(first);
+
+        // Keep subdividing random rectangles and placing rooms in them
+        for _ in 0..MAX_ATTEMPTS {
+            let rect = self.rects[rng.random_slice_index(&self.rects).unwrap()];
+            let candidate = self.random_room(rect, rng);
+            if self.is_possible(&candidate) {
+                self.map.apply_room_to_map(&candidate);
+                self.map.rooms.push(candidate);
+                self.add_subrects(rect);
+            }
+        }
+
+        // Connect the rooms, which are sorted from left to right for sequential corridors, then put the way down in the
+        // last room
+        self.map.rooms.sort_by_key(|room| room.upper_x);
+        connect_rooms(&mut self.map, self.corridors, rng);
+        place_stairs_in_last_room(&mut self.map);
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_starting_position(&self) -> Point {
+        let (x, y) = self.map.rooms[0].center();
+        Point::new(x, y)
+    }
+
+    fn get_spawn_regions(&self) -> Vec<Rectangle> {
+        room_spawn_regions(&self.map)
+    }
+
+    fn get_doors(&self) -> Vec<Point> {
+        room_door_locations(&self.map)
+    }
+}
@@ -0,0 +1,75 @@
+// cellular_automata.rs
+//
+// Builder for natural looking cave maps generated with a cellular automata.
+use rltk::{Point, RandomNumberGenerator};
+
+use super::{central_floor_tile, open_spawn_regions, remove_unreachable_and_place_stairs, MapBuilder};
+use crate::map::{Map, Rectangle, TileType};
+
+// Constants for the automata
+const FLOOR_PERCENT: i32 = 45;
+const ITERATIONS: i32 = 15;
+
+pub struct CellularAutomataBuilder {
+    map: Map,
+    start: Point,
+}
+
+impl CellularAutomataBuilder {
+    pub(super) fn new(depth: i32, width: i32, height: i32) -> CellularAutomataBuilder {
+        CellularAutomataBuilder { map: Map::new(depth, width, height), start: Point::new(0, 0) }
+    }
+
+    /// Run one generation of the automata, walls survive or spawn when crowded or isolated
+    fn iterate(&mut self) {
+        let mut next = self.map.tiles.clone();
+        let width = self.map.width as usize;
+        for y in 1..self.map.height - 1 {
+            for x in 1..self.map.width - 1 {
+                let idx = self.map.xy_idx(x, y);
+                let neighbors = [
+                    idx - 1, idx + 1, idx - width, idx + width,
+                    idx - width - 1, idx - width + 1, idx + width - 1, idx + width + 1
+                ].iter().filter(|ndx| self.map.tiles[**ndx] == TileType::Wall).count();
+
+                next[idx] = if neighbors > 4 || neighbors == 0 { TileType::Wall } else { TileType::Floor };
+            }
+        }
+        self.map.tiles = next;
+    }
+}
+
+impl MapBuilder for CellularAutomataBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        // Start with random noise, leaving the border as wall
+        for y in 1..self.map.height - 1 {
+            for x in 1..self.map.width - 1 {
+                let idx = self.map.xy_idx(x, y);
+                if rng.roll_dice(1, 100) <= FLOOR_PERCENT {
+                    self.map.tiles[idx] = TileType::Floor;
+                }
+            }
+        }
+
+        // Smooth the noise out in to caves
+        for _ in 0..ITERATIONS {
+            self.iterate();
+        }
+
+        // Start near the center, make sure everything can be reached from there, and the stairs are far away
+        self.start = central_floor_tile(&self.map);
+        remove_unreachable_and_place_stairs(&mut self.map, self.start);
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_starting_position(&self) -> Point {
+        self.start
+    }
+
+    fn get_spawn_regions(&self) -> Vec<Rectangle> {
+        open_spawn_regions(&self.map, self.start)
+    }
+}
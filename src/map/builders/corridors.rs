@@ -0,0 +1,251 @@
+// corridors.rs
+//
+// Ways of joining the rooms of a map together with corridors, and tidying up after them.
+use rltk::{LineAlg, Point, RandomNumberGenerator};
+
+use crate::map::{Map, TileType};
+
+/// One in this many rooms gets an extra corridor to its closest neighbour once a spanning tree has been dug
+const LOOP_CHANCE: i32 = 4;
+
+/// How the rooms are picked to be joined to each other
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum RoomConnection { Sequential, SpanningTree, NearestNeighbour }
+
+/// The corridors used for a map, which rooms are joined and whether the corridors cut diagonally between them
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct CorridorStyle {
+    pub connection: RoomConnection,
+    pub diagonal: bool,
+}
+
+impl CorridorStyle {
+    /// Return the style matching the name given, if any, a connection followed by "-diagonal" uses straight lines
+    pub fn from_name(name: &str) -> Option<CorridorStyle> {
+        let name = name.to_lowercase();
+        let (connection, diagonal) = match name.strip_suffix("-diagonal") {
+            Some(connection) => (connection, true),
+            None => (name.as_str(), false)
+        };
+        let connection = match connection {
+            "sequential" => RoomConnection::Sequential,
+            "mst" | "tree" => RoomConnection::SpanningTree,
+            "nearest" => RoomConnection::NearestNeighbour,
+            _ => return None
+        };
+        Some(CorridorStyle { connection, diagonal })
+    }
+
+    /// Select one of the styles at random
+    pub fn random(rng: &mut RandomNumberGenerator) -> CorridorStyle {
+        let connection = match rng.roll_dice(1, 3) {
+            1 => RoomConnection::Sequential,
+            2 => RoomConnection::SpanningTree,
+            _ => RoomConnection::NearestNeighbour
+        };
+        CorridorStyle { connection, diagonal: rng.roll_dice(1, 2) == 1 }
+    }
+}
+
+/// Dig corridors between the rooms of the map in the style given, then fill in any dead ends left behind
+pub fn connect_rooms(map: &mut Map, style: CorridorStyle, rng: &mut RandomNumberGenerator) {
+    let pairs = match style.connection {
+        RoomConnection::Sequential => (1..map.rooms.len()).map(|ndx| (ndx - 1, ndx)).collect(),
+        RoomConnection::SpanningTree => spanning_tree(map, rng),
+        RoomConnection::NearestNeighbour => nearest_neighbour_chain(map),
+    };
+    for (from, to) in pairs {
+        let (start, end) = (center(map, from), center(map, to));
+        if style.diagonal {
+            dig_diagonal(map, start, end);
+        } else if rng.range(0, 2) == 1 {
+            map.apply_horizontal_tunnel(start.x, end.x, start.y);
+            map.apply_vertical_tunnel(start.y, end.y, end.x);
+        } else {
+            map.apply_vertical_tunnel(start.y, end.y, start.x);
+            map.apply_horizontal_tunnel(start.x, end.x, end.y);
+        }
+    }
+    prune_dead_ends(map);
+}
+
+/// Return the center of the room as a point
+fn center(map: &Map, room: usize) -> Point {
+    let (x, y) = map.rooms[room].center();
+    Point::new(x, y)
+}
+
+/// Return the squared distance between the centers of two rooms
+fn distance(map: &Map, one: usize, two: usize) -> f32 {
+    rltk::DistanceAlg::PythagorasSquared.distance2d(center(map, one), center(map, two))
+}
+
+/// Join the rooms with the shortest corridors that still connect all of them, plus a few extra to make some loops
+fn spanning_tree(map: &Map, rng: &mut RandomNumberGenerator) -> Vec<(usize, usize)> {
+    // Prim's algorithm, grow the tree from the first room by the closest room not yet in it
+    let mut pairs: Vec<(usize, usize)> = Vec::new();
+    let mut in_tree = vec![false; map.rooms.len()];
+    if let Some(first) = in_tree.first_mut() { *first = true; }
+    for _ in 1..map.rooms.len() {
+        let closest = (0..map.rooms.len()).filter(|from| in_tree[*from])
+            .flat_map(|from| (0..map.rooms.len()).filter(|to| !in_tree[*to]).map(move |to| (from, to)))
+            .min_by(|a, b| distance(map, a.0, a.1).total_cmp(&distance(map, b.0, b.1)));
+        let Some((from, to)) = closest else { break };
+        in_tree[to] = true;
+        pairs.push((from, to));
+    }
+
+    // Some rooms get a second way in, to the closest room they aren't already joined to
+    for room in 0..map.rooms.len() {
+        if rng.roll_dice(1, LOOP_CHANCE) != 1 { continue; }
+        let joined = |other: usize| pairs.contains(&(room, other)) || pairs.contains(&(other, room));
+        let extra = (0..map.rooms.len()).filter(|other| *other != room && !joined(*other))
+            .min_by(|a, b| distance(map, room, *a).total_cmp(&distance(map, room, *b)));
+        if let Some(other) = extra {
+            pairs.push((room, other));
+        }
+    }
+    pairs
+}
+
+/// Join each room to the closest room that hasn't been visited yet, starting from the first
+fn nearest_neighbour_chain(map: &Map) -> Vec<(usize, usize)> {
+    let mut pairs: Vec<(usize, usize)> = Vec::new();
+    let mut visited = vec![false; map.rooms.len()];
+    let mut current = 0;
+    for _ in 1..map.rooms.len() {
+        visited[current] = true;
+        let next = (0..map.rooms.len()).filter(|room| !visited[*room])
+            .min_by(|a, b| distance(map, current, *a).total_cmp(&distance(map, current, *b)));
+        let Some(next) = next else { break };
+        pairs.push((current, next));
+        current = next;
+    }
+    pairs
+}
+
+/// Dig a straight corridor between the points, diagonal steps are widened so the corridor can be walked, and seen
+/// along, without cutting corners
+fn dig_diagonal(map: &mut Map, start: Point, end: Point) {
+    let mut previous = start;
+    for point in rltk::line2d(LineAlg::Bresenham, start, end) {
+        for tile in [point, Point::new(point.x, previous.y)] {
+            if tile.x < 1 || tile.x >= map.width - 1 || tile.y < 1 || tile.y >= map.height - 1 { continue; }
+            let idx = map.xy_idx(tile.x, tile.y);
+            map.tiles[idx] = TileType::Floor;
+        }
+        previous = point;
+    }
+}
+
+/// Fill in corridors that lead nowhere, a floor tile outside of the rooms with only one neighbour is a dead end
+fn prune_dead_ends(map: &mut Map) {
+    let mut in_room = vec![false; map.tiles.len()];
+    for room in map.rooms.iter() {
+        for y in room.upper_y + 1 ..= room.lower_y {
+            for x in room.upper_x + 1 ..= room.lower_x {
+                in_room[map.xy_idx(x, y)] = true;
+            }
+        }
+    }
+
+    // Filling in a dead end may leave the tile before it as one, so check the neighbours of anything filled in again
+    let mut open: Vec<Point> = (1..map.height - 1)
+        .flat_map(|y| (1..map.width - 1).map(move |x| Point::new(x, y)))
+        .collect();
+    while let Some(point) = open.pop() {
+        let idx = map.xy_idx(point.x, point.y);
+        if map.tiles[idx] != TileType::Floor || in_room[idx] { continue; }
+        let neighbours: Vec<Point> = (-1 ..= 1)
+            .flat_map(|delta_y| (-1 ..= 1).map(move |delta_x| Point::new(point.x + delta_x, point.y + delta_y)))
+            .filter(|neighbour| *neighbour != point && map.in_bounds(neighbour.x, neighbour.y))
+            .filter(|neighbour| map.tiles[map.xy_idx(neighbour.x, neighbour.y)] != TileType::Wall)
+            .collect();
+        if neighbours.len() <= 1 {
+            map.tiles[idx] = TileType::Wall;
+            open.extend(neighbours);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{map_stats, new_builder, BuilderType, MapBuilder, Rectangle};
+    use crate::{MAP_HEIGHT, MAP_WIDTH};
+
+    /// Every style of corridor, with and without diagonals
+    fn all_styles() -> Vec<CorridorStyle> {
+        [RoomConnection::Sequential, RoomConnection::SpanningTree, RoomConnection::NearestNeighbour].iter()
+            .flat_map(|connection| [false, true].map(|diagonal| CorridorStyle { connection: *connection, diagonal }))
+            .collect()
+    }
+
+    /// Return the builders for the room based maps, after building a map with the seed and corridor style given
+    fn built(seed: u64, style: CorridorStyle) -> Vec<Box<dyn MapBuilder>> {
+        [BuilderType::SimpleRooms, BuilderType::Bsp].iter()
+            .map(|builder| {
+                let mut builder = new_builder(*builder, 1, MAP_WIDTH, MAP_HEIGHT, style);
+                builder.build_map(&mut RandomNumberGenerator::seeded(seed));
+                builder
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_style_reaches_every_room() {
+        for style in all_styles() {
+            for seed in 1..=10 {
+                for builder in built(seed, style) {
+                    let stats = map_stats(&builder.get_map(), builder.get_starting_position());
+                    assert_eq!(stats.reachable_ratio(), 1.0, "{:?} seed {}", style, seed);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pruning_leaves_no_dead_ends_and_keeps_the_rooms() {
+        for style in all_styles() {
+            for seed in 1..=10 {
+                for builder in built(seed, style) {
+                    let map = builder.get_map();
+                    let walkable = |x: i32, y: i32| {
+                        map.in_bounds(x, y) && map.tiles[map.xy_idx(x, y)] != TileType::Wall
+                    };
+                    for room in map.rooms.iter() {
+                        let (x, y) = room.center();
+                        assert!(walkable(x, y), "{:?} seed {}: room center ({}, {}) was filled in", style, seed, x, y);
+                    }
+                    for (idx, _tile) in map.tiles.iter().enumerate().filter(|(_idx, tile)| **tile == TileType::Floor) {
+                        let point = map.idx_point(idx);
+                        let neighbours = (-1 ..= 1)
+                            .flat_map(|delta_y| (-1 ..= 1).map(move |delta_x| (delta_x, delta_y)))
+                            .filter(|delta| *delta != (0, 0) && walkable(point.x + delta.0, point.y + delta.1))
+                            .count();
+                        assert!(neighbours > 1, "{:?} seed {}: dead end at ({}, {})", style, seed, point.x, point.y);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pruning_fills_in_corridors_that_lead_nowhere() {
+        // Two rooms joined by a corridor, with a branch off it that goes nowhere
+        let mut map = Map::new(1, 40, 20);
+        for room in [Rectangle::new(2, 2, 6, 6), Rectangle::new(20, 2, 6, 6)] {
+            map.apply_room_to_map(&room);
+            map.rooms.push(room);
+        }
+        let (start, end) = (center(&map, 0), center(&map, 1));
+        map.apply_horizontal_tunnel(start.x, end.x, start.y);
+        map.apply_vertical_tunnel(start.y, 15, 14);
+
+        prune_dead_ends(&mut map);
+        let is_floor = |x: i32, y: i32| map.tiles[map.xy_idx(x, y)] == TileType::Floor;
+        assert!((start.x ..= end.x).all(|x| is_floor(x, start.y)));
+        // The first tile of the branch also touches the corridor diagonally, so it is a widening not a dead end
+        assert!((start.y + 2 ..= 15).all(|y| !is_floor(14, y)));
+    }
+}
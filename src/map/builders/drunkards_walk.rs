@@ -0,0 +1,69 @@
+// drunkards_walk.rs
+//
+// Builder for winding tunnel maps, dug out by diggers wandering at random.
+use rltk::{Point, RandomNumberGenerator};
+
+use super::{open_spawn_regions, remove_unreachable_and_place_stairs, MapBuilder};
+use crate::map::{Map, Rectangle, TileType};
+
+// Constants for the diggers
+const FLOOR_PERCENT: usize = 40;
+const DIGGER_LIFETIME: i32 = 400;
+
+pub struct DrunkardsWalkBuilder {
+    map: Map,
+    start: Point,
+}
+
+impl DrunkardsWalkBuilder {
+    pub(super) fn new(depth: i32, width: i32, height: i32) -> DrunkardsWalkBuilder {
+        DrunkardsWalkBuilder { map: Map::new(depth, width, height), start: Point::new(0, 0) }
+    }
+}
+
+impl MapBuilder for DrunkardsWalkBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        // Dig out the starting location in the center of the map
+        self.start = Point::new(self.map.width / 2, self.map.height / 2);
+        let start_idx = self.map.xy_idx(self.start.x, self.start.y);
+        self.map.tiles[start_idx] = TileType::Floor;
+
+        // Keep sending out diggers until enough of the map is floor, after the first they start somewhere already dug
+        // so that large maps get filled out rather than the area around the start
+        let desired = self.map.tiles.len() * FLOOR_PERCENT / 100;
+        let mut dug = vec![start_idx];
+        while dug.len() < desired {
+            let origin = if dug.len() == 1 { start_idx } else { dug[rng.roll_dice(1, dug.len() as i32) as usize - 1] };
+            let mut x = origin as i32 % self.map.width;
+            let mut y = origin as i32 / self.map.width;
+            for _ in 0..DIGGER_LIFETIME {
+                let idx = self.map.xy_idx(x, y);
+                if self.map.tiles[idx] != TileType::Floor {
+                    self.map.tiles[idx] = TileType::Floor;
+                    dug.push(idx);
+                }
+                match rng.roll_dice(1, 4) {
+                    1 => if x > 2 { x -= 1; },
+                    2 => if x < self.map.width - 2 { x += 1; },
+                    3 => if y > 2 { y -= 1; },
+                    _ => if y < self.map.height - 2 { y += 1; },
+                }
+            }
+        }
+
+        // Everything is dug from the start, but this also finds the most distant tile for the stairs
+        remove_unreachable_and_place_stairs(&mut self.map, self.start);
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_starting_position(&self) -> Point {
+        self.start
+    }
+
+    fn get_spawn_regions(&self) -> Vec<Rectangle> {
+        open_spawn_regions(&self.map, self.start)
+    }
+}
@@ -0,0 +1,180 @@
+// mod.rs
+//
+// Define the map builders, and the trait they share.
+use rltk::{Point, RandomNumberGenerator};
+
+use super::{Map, Rectangle, TileType};
+
+mod bsp;
+pub use bsp::BspBuilder;
+mod cellular_automata;
+pub use cellular_automata::CellularAutomataBuilder;
+mod corridors;
+use corridors::connect_rooms;
+pub use corridors::{CorridorStyle, RoomConnection};
+mod drunkards_walk;
+pub use drunkards_walk::DrunkardsWalkBuilder;
+mod simple_rooms;
+pub use simple_rooms::SimpleRoomsBuilder;
+mod validation;
+pub use validation::{map_stats, validate_map, MapStats};
+
+/// The size of the areas that maps without rooms are divided in to for spawning
+const OPEN_REGION_SIZE: i32 = 12;
+
+/// Trait shared by all of the dungeon generation algorithms
+pub trait MapBuilder {
+    /// Generate the map using the RNG provided
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator);
+
+    /// Return a copy of the map that was built
+    fn get_map(&self) -> Map;
+
+    /// Return the location the player should start at
+    fn get_starting_position(&self) -> Point;
+
+    /// Return the regions that monsters and items may be spawned in, the player's starting area is excluded
+    fn get_spawn_regions(&self) -> Vec<Rectangle>;
+
+    /// Return the locations that doors should be placed at, there are none unless the map has rooms to put them in
+    fn get_doors(&self) -> Vec<Point> {
+        Vec::new()
+    }
+}
+
+/// The dungeon generation algorithms that are available
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum BuilderType { SimpleRooms, Bsp, CellularAutomata, DrunkardsWalk }
+
+impl BuilderType {
+    /// Return the builder type matching the name given, if any
+    pub fn from_name(name: &str) -> Option<BuilderType> {
+        match name.to_lowercase().as_str() {
+            "rooms" | "simple" => Some(BuilderType::SimpleRooms),
+            "bsp" => Some(BuilderType::Bsp),
+            "caves" | "cellular" => Some(BuilderType::CellularAutomata),
+            "drunkard" | "drunkards" => Some(BuilderType::DrunkardsWalk),
+            _ => None
+        }
+    }
+
+    /// Select one of the builder types at random
+    pub fn random(rng: &mut RandomNumberGenerator) -> BuilderType {
+        match rng.roll_dice(1, 4) {
+            1 => BuilderType::SimpleRooms,
+            2 => BuilderType::Bsp,
+            3 => BuilderType::CellularAutomata,
+            _ => BuilderType::DrunkardsWalk
+        }
+    }
+}
+
+/// Return a new builder of the type requested for a map of the given depth and size, the corridor style is only used
+/// by builders that have rooms
+pub fn new_builder(builder: BuilderType, depth: i32, width: i32, height: i32, corridors: CorridorStyle)
+        -> Box<dyn MapBuilder> {
+    match builder {
+        BuilderType::SimpleRooms => Box::new(SimpleRoomsBuilder::new(depth, width, height, corridors)),
+        BuilderType::Bsp => Box::new(BspBuilder::new(depth, width, height, corridors)),
+        BuilderType::CellularAutomata => Box::new(CellularAutomataBuilder::new(depth, width, height)),
+        BuilderType::DrunkardsWalk => Box::new(DrunkardsWalkBuilder::new(depth, width, height))
+    }
+}
+
+/// Spawn regions for room based maps, every room except the first
+fn room_spawn_regions(map: &Map) -> Vec<Rectangle> {
+    map.rooms.iter().skip(1).copied().collect()
+}
+
+/// Door locations for room based maps, anywhere a corridor passes through the wall around a room
+fn room_door_locations(map: &Map) -> Vec<Point> {
+    let mut doors: Vec<Point> = Vec::new();
+    for room in map.rooms.iter() {
+        // The floor of a room starts one tile in, so the walls are on the upper bounds and one past the lower bounds
+        let mut wall = Vec::new();
+        for x in room.upper_x ..= room.lower_x + 1 {
+            wall.push(Point::new(x, room.upper_y));
+            wall.push(Point::new(x, room.lower_y + 1));
+        }
+        for y in room.upper_y + 1 ..= room.lower_y {
+            wall.push(Point::new(room.upper_x, y));
+            wall.push(Point::new(room.lower_x + 1, y));
+        }
+
+        for point in wall {
+            if is_doorway(map, point) && !doors.contains(&point) {
+                doors.push(point);
+            }
+        }
+    }
+    doors
+}
+
+/// Returns true if the location is a floor tile with walls on either side of it, so a door fits
+fn is_doorway(map: &Map, point: Point) -> bool {
+    if point.x < 1 || point.x >= map.width - 1 || point.y < 1 || point.y >= map.height - 1 {
+        return false;
+    }
+    let is_wall = |x: i32, y: i32| map.tiles[map.xy_idx(x, y)] == TileType::Wall;
+    if map.tiles[map.xy_idx(point.x, point.y)] != TileType::Floor {
+        return false;
+    }
+    (is_wall(point.x - 1, point.y) && is_wall(point.x + 1, point.y)) ||
+    (is_wall(point.x, point.y - 1) && is_wall(point.x, point.y + 1))
+}
+
+/// Place the down stairs in the center of the last room
+fn place_stairs_in_last_room(map: &mut Map) {
+    if let Some(room) = map.rooms.last() {
+        let (x, y) = room.center();
+        let idx = map.xy_idx(x, y);
+        map.tiles[idx] = TileType::DownStairs;
+    }
+}
+
+/// Spawn regions for open maps, the map is divided in to a grid of areas less the one containing the start
+fn open_spawn_regions(map: &Map, start: Point) -> Vec<Rectangle> {
+    let mut regions = Vec::new();
+    for y in (1..map.height - 1).step_by(OPEN_REGION_SIZE as usize) {
+        for x in (1..map.width - 1).step_by(OPEN_REGION_SIZE as usize) {
+            // Offset by one since only the interior of a rectangle is used
+            let region = Rectangle::new(x - 1, y - 1, OPEN_REGION_SIZE, OPEN_REGION_SIZE);
+            if start.x >= x && start.x < x + OPEN_REGION_SIZE && start.y >= y && start.y < y + OPEN_REGION_SIZE {
+                continue;
+            }
+            regions.push(region);
+        }
+    }
+    regions
+}
+
+/// Find the floor tile closest to the center of the map
+fn central_floor_tile(map: &Map) -> Point {
+    let center = Point::new(map.width / 2, map.height / 2);
+    let distance = |point: &Point| rltk::DistanceAlg::PythagorasSquared.distance2d(*point, center);
+    map.tiles.iter().enumerate()
+        .filter(|(_, tile)| **tile == TileType::Floor)
+        .map(|(idx, _)| Point::new(idx as i32 % map.width, idx as i32 / map.width))
+        .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+        .unwrap_or(center)
+}
+
+/// Wall off any floor tiles that can't be reached from the start, and put the down stairs on the most distant one
+fn remove_unreachable_and_place_stairs(map: &mut Map, start: Point) {
+    // The pathing uses the occupied tiles, so make sure they reflect the walls
+    map.populate_occupied();
+    let starts = vec![map.xy_idx(start.x, start.y)];
+    let dijkstra = rltk::DijkstraMap::new(map.width, map.height, &starts, map, 1000.0);
+
+    let mut stairs = (starts[0], 0.0f32);
+    for (idx, tile) in map.tiles.iter_mut().enumerate() {
+        if *tile != TileType::Floor { continue; }
+        let distance = dijkstra.map[idx];
+        if distance == f32::MAX {
+            *tile = TileType::Wall;
+        } else if distance > stairs.1 {
+            stairs = (idx, distance);
+        }
+    }
+    map.tiles[stairs.0] = TileType::DownStairs;
+}
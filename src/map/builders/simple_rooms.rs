@@ -0,0 +1,113 @@
+// simple_rooms.rs
+//
+// Builder for the original rooms and corridors maps.
+use rltk::{Point, RandomNumberGenerator};
+
+use super::{connect_rooms, place_stairs_in_last_room, room_door_locations, room_spawn_regions, CorridorStyle,
+            MapBuilder};
+use crate::map::{Map, Rectangle};
+
+// Constants for the rooms
+const MAX_ROOMS: i32 = 30;
+const MIN_SIZE: i32 = 6;
+const MAX_SIZE: i32 = 10;
+
+pub struct SimpleRoomsBuilder {
+    map: Map,
+    corridors: CorridorStyle,
+}
+
+impl SimpleRoomsBuilder {
+    pub(super) fn new(depth: i32, width: i32, height: i32, corridors: CorridorStyle) -> SimpleRoomsBuilder {
+        SimpleRoomsBuilder { map: Map::new(depth, width, height), corridors }
+    }
+}
+
+impl MapBuilder for SimpleRoomsBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        let map = &mut self.map;
+        for _idx in 0..MAX_ROOMS {
+            // Create a new room
+            let width = rng.range(MIN_SIZE, MAX_SIZE);
+            let height = rng.range(MIN_SIZE, MAX_SIZE);
+            let x = rng.roll_dice(1, map.width - width - 1) - 1;
+            let y = rng.roll_dice(1, map.height - height - 1) - 1;
+            let new_room = Rectangle::new(x, y, width, height);
+
+            // Check to see if the room can be placed, if not try again
+            if map.rooms.iter().any(|other_room| new_room.intersect(other_room)) {
+                continue;
+            }
+
+            // The location is valid, add the room
+            map.apply_room_to_map(&new_room);
+            map.rooms.push(new_room);
+        }
+
+        // Join the rooms up, then put the way down in the last room
+        connect_rooms(map, self.corridors, rng);
+        place_stairs_in_last_room(map);
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_starting_position(&self) -> Point {
+        let (x, y) = self.map.rooms[0].center();
+        Point::new(x, y)
+    }
+
+    fn get_spawn_regions(&self) -> Vec<Rectangle> {
+        room_spawn_regions(&self.map)
+    }
+
+    fn get_doors(&self) -> Vec<Point> {
+        room_door_locations(&self.map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{map_stats, CorridorStyle, TileType};
+    use crate::{MAP_HEIGHT, MAP_WIDTH};
+
+    /// Return the builder after building a map with the seed given, the corridor style is picked by the seed too
+    fn built(seed: u64) -> SimpleRoomsBuilder {
+        let mut rng = RandomNumberGenerator::seeded(seed);
+        let corridors = CorridorStyle::random(&mut rng);
+        let mut builder = SimpleRoomsBuilder::new(1, MAP_WIDTH, MAP_HEIGHT, corridors);
+        builder.build_map(&mut rng);
+        builder
+    }
+
+    #[test]
+    fn rooms_never_intersect() {
+        for seed in 1..=20 {
+            let rooms = built(seed).map.rooms;
+            for (idx, room) in rooms.iter().enumerate() {
+                assert!(rooms[idx + 1 ..].iter().all(|other| !room.intersect(other)), "seed {}", seed);
+            }
+        }
+    }
+
+    #[test]
+    fn stairs_are_in_the_last_room() {
+        for seed in 1..=20 {
+            let map = built(seed).map;
+            let (x, y) = map.rooms.last().unwrap().center();
+            assert!(map.tiles[map.xy_idx(x, y)] == TileType::DownStairs, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn every_room_can_be_reached() {
+        // The corridors alone should join everything up, without any help from validation
+        for seed in 1..=20 {
+            let builder = built(seed);
+            let stats = map_stats(&builder.get_map(), builder.get_starting_position());
+            assert_eq!(stats.reachable_ratio(), 1.0, "seed {}", seed);
+        }
+    }
+}
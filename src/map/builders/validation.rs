@@ -0,0 +1,119 @@
+// validation.rs
+//
+// Check that everything on a finished map can be reached from the start, and fix it up when it can't.
+use std::collections::VecDeque;
+
+use rltk::Point;
+
+use crate::map::{Map, TileType};
+
+/// Unreachable areas at least this big are connected to the rest of the map, anything smaller is filled in
+const MIN_CONNECT_SIZE: usize = 9;
+
+/// Statistics describing a map, as seen from the start
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct MapStats {
+    pub rooms: usize,
+    pub floor_tiles: usize,
+    pub reachable_tiles: usize,
+    pub connected_regions: usize,
+    pub culled_regions: usize,
+}
+
+impl MapStats {
+    /// Return the fraction of the floor that can be reached from the start
+    pub fn reachable_ratio(&self) -> f32 {
+        if self.floor_tiles == 0 { return 0.0; }
+        self.reachable_tiles as f32 / self.floor_tiles as f32
+    }
+}
+
+/// Make sure every floor tile can be reached from the start, unreachable areas are either connected with a corridor
+/// or filled in, depending on their size, and the stairs are always connected
+pub fn validate_map(map: &mut Map, start: Point) -> MapStats {
+    let (mut connected_regions, mut culled_regions) = (0, 0);
+    loop {
+        let reachable = flood_fill(map, &[map.xy_idx(start.x, start.y)]);
+        let Some(first) = (0..map.tiles.len()).find(|idx| is_walkable(map.tiles[*idx]) && !reachable[*idx]) else {
+            break;
+        };
+
+        // Deal with one region at a time, then check again since connecting a region may have joined others
+        let region = flood_fill(map, &[first]);
+        let tiles: Vec<usize> = (0..map.tiles.len()).filter(|idx| region[*idx]).collect();
+        let has_stairs = tiles.iter().any(|idx| map.tiles[*idx] == TileType::DownStairs);
+        if has_stairs || tiles.len() >= MIN_CONNECT_SIZE {
+            connect(map, map.idx_point(first), &reachable);
+            connected_regions += 1;
+        } else {
+            for idx in tiles {
+                map.tiles[idx] = TileType::Wall;
+            }
+            culled_regions += 1;
+        }
+    }
+
+    MapStats { connected_regions, culled_regions, ..map_stats(map, start) }
+}
+
+/// Return the statistics for the map as it is, as seen from the point given
+pub fn map_stats(map: &Map, start: Point) -> MapStats {
+    let reachable = flood_fill(map, &[map.xy_idx(start.x, start.y)]);
+    MapStats {
+        rooms: map.rooms.len(),
+        floor_tiles: map.tiles.iter().filter(|tile| is_walkable(**tile)).count(),
+        reachable_tiles: reachable.iter().filter(|reached| **reached).count(),
+        connected_regions: 0,
+        culled_regions: 0,
+    }
+}
+
+/// Returns true if the tile can be walked on
+fn is_walkable(tile: TileType) -> bool {
+    tile != TileType::Wall
+}
+
+/// Return which tiles can be reached from the starting tiles, using the same eight directions as movement
+fn flood_fill(map: &Map, starts: &[usize]) -> Vec<bool> {
+    let mut reached = vec![false; map.tiles.len()];
+    let mut open: VecDeque<usize> = VecDeque::new();
+    for start in starts.iter() {
+        reached[*start] = true;
+        open.push_back(*start);
+    }
+    while let Some(idx) = open.pop_front() {
+        let here = map.idx_point(idx);
+        for delta_y in -1 ..= 1 {
+            for delta_x in -1 ..= 1 {
+                let (x, y) = (here.x + delta_x, here.y + delta_y);
+                if !map.in_bounds(x, y) { continue; }
+                let next = map.xy_idx(x, y);
+                if !reached[next] && is_walkable(map.tiles[next]) {
+                    reached[next] = true;
+                    open.push_back(next);
+                }
+            }
+        }
+    }
+    reached
+}
+
+/// Dig an L-shaped corridor from the point to the closest reachable tile, only walls are dug out so the stairs stay
+fn connect(map: &mut Map, from: Point, reachable: &[bool]) {
+    let distance = |point: &Point| rltk::DistanceAlg::PythagorasSquared.distance2d(*point, from);
+    let Some(to) = (0..map.tiles.len())
+        .filter(|idx| reachable[*idx])
+        .map(|idx| map.idx_point(idx))
+        .min_by(|a, b| distance(a).total_cmp(&distance(b))) else { return };
+
+    let mut corridor: Vec<Point> = (i32::min(from.x, to.x) ..= i32::max(from.x, to.x))
+        .map(|x| Point::new(x, from.y))
+        .collect();
+    corridor.extend((i32::min(from.y, to.y) ..= i32::max(from.y, to.y)).map(|y| Point::new(to.x, y)));
+    for point in corridor {
+        let idx = map.xy_idx(point.x, point.y);
+        if map.tiles[idx] == TileType::Wall {
+            map.tiles[idx] = TileType::Floor;
+        }
+    }
+}
@@ -1,7 +1,9 @@
 // rectangle.rs
 //
 // Define the rectangle structure and functions that will be useful with it.
+use serde::{Deserialize, Serialize};
 
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct Rectangle {
     pub upper_x: i32,
     pub upper_y: i32,
@@ -1,15 +1,11 @@
 // player.rs
 //
 // Management of the player.
-use std::cmp::{min, max};
-
-use rltk::{Point, Rltk, VirtualKeyCode};
+use rltk::{Point, RandomNumberGenerator, Rltk, VirtualKeyCode};
 use specs::prelude::*;
 
-use super::{Map, Player, Position, RunState, State, Viewshed};
-
-use crate::map::WINDOW_HEIGHT as WINDOW_HEIGHT;
-use crate::map::WINDOW_WIDTH as WINDOW_WIDTH;
+use super::{AreaOfEffect, BlocksVisibility, CombatStats, Door, Equipped, GameLog, InBackpack, Item, Map, Monster, OccupiesTile, Player, Position, Ranged, Renderable, RunState, State, StatusEffects, StatusKind, TileType, Viewshed,
+            WantsToDropItem, WantsToMelee, WantsToPickupItem, WantsToRemoveItem, WantsToUseItem};
 
 /// The default field-of-vision (FOV) for a new player character, in tiles
 pub const DEFAULT_FOV: i32 = 8;
@@ -19,20 +15,49 @@ fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
     let mut positions = ecs.write_storage::<Position>();
     let mut players = ecs.write_storage::<Player>();
     let mut viewsheds = ecs.write_storage::<Viewshed>();
-    let map = ecs.fetch::<Map>();
+    let combat_stats = ecs.read_storage::<CombatStats>();
+    let mut wants_melee = ecs.write_storage::<WantsToMelee>();
+    let mut doors = ecs.write_storage::<Door>();
+    let mut occupiers = ecs.write_storage::<OccupiesTile>();
+    let mut blockers = ecs.write_storage::<BlocksVisibility>();
+    let mut renderables = ecs.write_storage::<Renderable>();
+    let entities = ecs.entities();
+    let mut map = ecs.fetch_mut::<Map>();
+
+    for (entity, _player, pos, viewshed) in (&entities, &mut players, &mut positions, &mut viewsheds).join() {
+        // Get the target location, the edge of the map can't be crossed
+        let (x, y) = (pos.x + delta_x, pos.y + delta_y);
+        if !map.in_bounds(x, y) {
+            return;
+        }
+        let destination_idx = map.xy_idx(x, y);
+
+        // Attack anything in the way that can be fought, stumbling in place is no reason to attack yourself
+        for potential_target in map.tile_content[destination_idx].iter().filter(|target| **target != entity) {
+            if combat_stats.get(*potential_target).is_some() {
+                wants_melee.insert(entity, WantsToMelee{ target: *potential_target }).expect("Unable to insert attack");
+                return;
+            }
+        }
 
-    for (_player, pos, viewshed) in (&mut players, &mut positions, &mut viewsheds).join() {
-        // Get the target location
-        let destination_idx = map.xy_idx(pos.x + delta_x, pos.y + delta_y);
+        // Open any closed door in the way, which takes the turn, the player can see through the doorway right away
+        if map.open_door(destination_idx, &mut doors, &mut occupiers, &mut blockers, &mut renderables) {
+            viewshed.dirty = true;
+            ecs.write_resource::<GameLog>().add("You open the door.");
+            return;
+        }
 
         // Don't let the player walk though things
         if map.occupied_tiles[destination_idx] {
             return;
         }
 
-        // Apply the movement to the player
-        pos.x = min(WINDOW_WIDTH , max(0, pos.x + delta_x));
-        pos.y = min(WINDOW_HEIGHT, max(0, pos.y + delta_y));
+        // Apply the movement to the player, and update the occupied tiles so monsters can't step in to their place
+        let from_idx = map.xy_idx(pos.x, pos.y);
+        map.occupied_tiles[from_idx] = false;
+        map.occupied_tiles[destination_idx] = true;
+        pos.x = x;
+        pos.y = y;
 
         // The everyone know where the player is
         let mut player_pos = ecs.write_resource::<Point>();
@@ -44,33 +69,245 @@ fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
     }
 }
 
+/// The actions the player can take on their turn
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Action {
+    Move(i32, i32),
+    Wait,
+    Descend,
+    Pickup,
+    UseItem(usize),
+    UseItemAt(usize, Point),
+    DropItem(usize),
+    RemoveItem(usize)
+}
+
+/// Apply the action the player selected, and return the new state
+pub fn player_action(gs: &mut State, action: Action) -> RunState {
+    match action {
+        Action::Move(mut delta_x, mut delta_y) => {
+            // Confusion sends the player off in a random direction
+            if player_has(&gs.ecs, StatusKind::Confused) {
+                let mut rng = gs.ecs.write_resource::<RandomNumberGenerator>();
+                delta_x = rng.range(-1, 2);
+                delta_y = rng.range(-1, 2);
+                gs.ecs.write_resource::<GameLog>().add("You stumble around in confusion.");
+            }
+            try_move_player(delta_x, delta_y, &mut gs.ecs);
+        }
+        Action::Wait => {}      // Just let the turn pass
+        Action::Descend => {
+            if try_descend(&gs.ecs) {
+                return RunState::NextLevel;
+            }
+            gs.ecs.write_resource::<GameLog>().add("There is no way down from here.");
+            return RunState::Paused;
+        }
+        Action::Pickup => {
+            if !try_pickup(&gs.ecs) {
+                return RunState::Paused;
+            }
+        }
+        Action::UseItem(ndx) => {
+            let Some(item) = backpack_items(&gs.ecs).get(ndx).copied() else { return RunState::Paused };
+
+            // Ranged items need a target before they can be used
+            if let Some(ranged) = gs.ecs.read_storage::<Ranged>().get(item) {
+                let radius = gs.ecs.read_storage::<AreaOfEffect>().get(item).map_or(0, |aoe| aoe.radius);
+                let player_pos = *gs.ecs.fetch::<Point>();
+                let cursor = default_target(&gs.ecs, ranged.range).unwrap_or(player_pos);
+                return RunState::ShowTargeting { slot: ndx, range: ranged.range, radius, cursor };
+            }
+
+            let player_entity = *gs.ecs.fetch::<Entity>();
+            gs.ecs.write_storage::<WantsToUseItem>()
+                .insert(player_entity, WantsToUseItem{ item, target: None }).expect("Unable to insert intent");
+        }
+        Action::UseItemAt(ndx, target) => {
+            let Some(item) = backpack_items(&gs.ecs).get(ndx).copied() else { return RunState::Paused };
+            let range = gs.ecs.read_storage::<Ranged>().get(item).map_or(0, |ranged| ranged.range);
+            if !valid_targets(&gs.ecs, range).contains(&target) {
+                gs.ecs.write_resource::<GameLog>().add("You can't target that.");
+                return RunState::Paused;
+            }
+            let player_entity = *gs.ecs.fetch::<Entity>();
+            gs.ecs.write_storage::<WantsToUseItem>()
+                .insert(player_entity, WantsToUseItem{ item, target: Some(target) }).expect("Unable to insert intent");
+        }
+        Action::DropItem(ndx) => {
+            let Some(item) = backpack_items(&gs.ecs).get(ndx).copied() else { return RunState::Paused };
+            let player_entity = *gs.ecs.fetch::<Entity>();
+            gs.ecs.write_storage::<WantsToDropItem>()
+                .insert(player_entity, WantsToDropItem{ item }).expect("Unable to insert intent");
+        }
+        Action::RemoveItem(ndx) => {
+            let Some(item) = equipped_items(&gs.ecs).get(ndx).copied() else { return RunState::Paused };
+            let player_entity = *gs.ecs.fetch::<Entity>();
+            gs.ecs.write_storage::<WantsToRemoveItem>()
+                .insert(player_entity, WantsToRemoveItem{ item }).expect("Unable to insert intent");
+        }
+    }
+    RunState::Running
+}
+
+/// Returns true if the player is under the status effect
+fn player_has(ecs: &World, kind: StatusKind) -> bool {
+    let player_entity = *ecs.fetch::<Entity>();
+    ecs.read_storage::<StatusEffects>().get(player_entity).is_some_and(|statuses| statuses.has(kind))
+}
+
+/// Return the items the player is carrying, in the order they are listed in the inventory
+pub fn backpack_items(ecs: &World) -> Vec<Entity> {
+    let player_entity = *ecs.fetch::<Entity>();
+    let backpack = ecs.read_storage::<InBackpack>();
+    let entities = ecs.entities();
+    (&entities, &backpack).join()
+        .filter(|(_item, pack)| pack.owner == player_entity)
+        .map(|(item, _pack)| item)
+        .collect()
+}
+
+/// Return the items the player is wearing, in the order they are listed when taking them off
+pub fn equipped_items(ecs: &World) -> Vec<Entity> {
+    let player_entity = *ecs.fetch::<Entity>();
+    let equipped = ecs.read_storage::<Equipped>();
+    let entities = ecs.entities();
+    (&entities, &equipped).join()
+        .filter(|(_item, worn)| worn.owner == player_entity)
+        .map(|(item, _worn)| item)
+        .collect()
+}
+
+/// Return the tiles the player can see that are within range of them
+pub fn valid_targets(ecs: &World, range: i32) -> Vec<Point> {
+    let player_pos = *ecs.fetch::<Point>();
+    let player_entity = *ecs.fetch::<Entity>();
+    let viewsheds = ecs.read_storage::<Viewshed>();
+    let Some(viewshed) = viewsheds.get(player_entity) else { return Vec::new() };
+    viewshed.visible_tiles.iter()
+        .filter(|tile| rltk::DistanceAlg::Pythagoras.distance2d(player_pos, **tile) <= range as f32)
+        .copied()
+        .collect()
+}
+
+/// Return the location of the closest monster the player can target, if there is one
+pub fn default_target(ecs: &World, range: i32) -> Option<Point> {
+    let player_pos = *ecs.fetch::<Point>();
+    let targets = valid_targets(ecs, range);
+    let monsters = ecs.read_storage::<Monster>();
+    let positions = ecs.read_storage::<Position>();
+    (&monsters, &positions).join()
+        .map(|(_monster, pos)| Point::new(pos.x, pos.y))
+        .filter(|point| targets.contains(point))
+        .min_by(|a, b| {
+            let distance = |point: &Point| rltk::DistanceAlg::PythagorasSquared.distance2d(player_pos, *point);
+            distance(a).total_cmp(&distance(b))
+        })
+}
+
+/// Try to pick up an item the player is standing on, returns true if there was one
+fn try_pickup(ecs: &World) -> bool {
+    let player_pos = ecs.fetch::<Point>();
+    let player_entity = ecs.fetch::<Entity>();
+    let entities = ecs.entities();
+    let items = ecs.read_storage::<Item>();
+    let positions = ecs.read_storage::<Position>();
+
+    let target = (&entities, &items, &positions).join()
+        .find(|(_item_entity, _item, pos)| pos.x == player_pos.x && pos.y == player_pos.y)
+        .map(|(item_entity, _item, _pos)| item_entity);
+
+    match target {
+        Some(item) => {
+            let mut pickup = ecs.write_storage::<WantsToPickupItem>();
+            pickup.insert(*player_entity, WantsToPickupItem{ collected_by: *player_entity, item }).expect("Unable to insert intent");
+            true
+        }
+        None => {
+            ecs.write_resource::<GameLog>().add("There is nothing here to pick up.");
+            false
+        }
+    }
+}
+
+/// Returns true if the player is standing on the down stairs
+fn try_descend(ecs: &World) -> bool {
+    let player_pos = ecs.fetch::<Point>();
+    let map = ecs.fetch::<Map>();
+    map.tiles[map.xy_idx(player_pos.x, player_pos.y)] == TileType::DownStairs
+}
+
+/// Return the direction the key points in, if it is one of the movement keys
+pub fn key_direction(key: VirtualKeyCode) -> Option<(i32, i32)> {
+    match key {
+        // Up, down, left, right movement
+        VirtualKeyCode::Left |
+        VirtualKeyCode::Numpad4 => Some((-1, 0)),
+        VirtualKeyCode::Right |
+        VirtualKeyCode::Numpad6 => Some((1, 0)),
+        VirtualKeyCode::Up |
+        VirtualKeyCode::Numpad8 => Some((0, -1)),
+        VirtualKeyCode::Down |
+        VirtualKeyCode::Numpad2 => Some((0, 1)),
+
+        // Diagonal movement
+        VirtualKeyCode::Numpad7 => Some((-1, -1)),
+        VirtualKeyCode::Numpad9 => Some((1, -1)),
+        VirtualKeyCode::Numpad3 => Some((1, 1)),
+        VirtualKeyCode::Numpad1 => Some((-1, 1)),
+        _ => None
+    }
+}
+
 /// Handle the player input
 pub fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
-    // Player movement
-    match ctx.key {
-        None => {
-            // Nothing happened
-            return RunState::Paused
+    // Nothing happened
+    let Some(key) = ctx.key else { return RunState::Paused };
+
+    // Movement
+    if let Some((delta_x, delta_y)) = key_direction(key) {
+        return player_action(gs, Action::Move(delta_x, delta_y));
+    }
+
+    // Map the key to the action to take
+    let action = match key {
+        // Skip a turn
+        VirtualKeyCode::Numpad5 |
+        VirtualKeyCode::Space => Action::Wait,
+
+        // Take the stairs
+        VirtualKeyCode::Period => Action::Descend,
+
+        // Items
+        VirtualKeyCode::G => Action::Pickup,
+        VirtualKeyCode::I => return RunState::ShowInventory,
+        VirtualKeyCode::D => return RunState::ShowDropItem,
+        VirtualKeyCode::R => return RunState::ShowRemoveItem,
+
+        // Save and quit
+        VirtualKeyCode::Escape => return RunState::SaveGame,
+
+        _ => return RunState::Paused     // Ignore anything else
+    };
+    player_action(gs, action)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn confused_player_never_attacks_themselves() {
+        // With the monsters gone the only thing the player could hurt is themselves
+        let mut gs = State::for_tests(1234);
+        gs.keep_monsters(0);
+        let player = *gs.ecs.fetch::<Entity>();
+        StatusEffects::new_effect(&mut gs.ecs.write_storage::<StatusEffects>(), player, StatusKind::Confused, 1000);
+
+        for _ in 0..100 {
+            player_action(&mut gs, Action::Move(1, 0));
+            gs.run_until_player_turn();
         }
-        Some(key) => match key {
-            // Up, down, left, right movement
-            VirtualKeyCode::Left |
-            VirtualKeyCode::Numpad4 => try_move_player(-1, 0, &mut gs.ecs),
-            VirtualKeyCode::Right |
-            VirtualKeyCode::Numpad6 => try_move_player(1, 0, &mut gs.ecs),
-            VirtualKeyCode::Up |
-            VirtualKeyCode::Numpad8 => try_move_player(0, -1, &mut gs.ecs),
-            VirtualKeyCode::Down |
-            VirtualKeyCode::Numpad2 => try_move_player(0, 1, &mut gs.ecs),
-
-            // Diagonal movement
-            VirtualKeyCode::Numpad7 => try_move_player(-1, -1, &mut gs.ecs),
-            VirtualKeyCode::Numpad9 => try_move_player(1, -1, &mut gs.ecs),
-            VirtualKeyCode::Numpad3 => try_move_player(1, 1, &mut gs.ecs),
-            VirtualKeyCode::Numpad1 => try_move_player(-1, 1, &mut gs.ecs),
-
-            _ => {}     // Ignore anything else
-        },
+        let stats = gs.ecs.read_storage::<CombatStats>();
+        assert_eq!(stats.get(player).unwrap().hp, stats.get(player).unwrap().max_hp);
     }
-    RunState::Running
-}
\ No newline at end of file
+}
@@ -0,0 +1,45 @@
+// random_table.rs
+//
+// Define a table of weighted entries that can be rolled against.
+use rltk::RandomNumberGenerator;
+
+/// A single entry in the table
+pub struct RandomEntry {
+    name: String,
+    weight: i32,
+}
+
+/// Table of names, each with the weight given to it when rolling
+#[derive(Default)]
+pub struct RandomTable {
+    entries: Vec<RandomEntry>,
+    total_weight: i32,
+}
+
+impl RandomTable {
+    pub fn new() -> RandomTable {
+        RandomTable::default()
+    }
+
+    /// Add the entry to the table, entries without a positive weight are never rolled
+    pub fn add<S: ToString>(mut self, name: S, weight: i32) -> RandomTable {
+        if weight > 0 {
+            self.total_weight += weight;
+            self.entries.push(RandomEntry{ name: name.to_string(), weight });
+        }
+        self
+    }
+
+    /// Roll against the table, returning the name selected or None if the table is empty
+    pub fn roll(&self, rng: &mut RandomNumberGenerator) -> Option<String> {
+        if self.total_weight == 0 { return None; }
+        let mut roll = rng.roll_dice(1, self.total_weight) - 1;
+        for entry in self.entries.iter() {
+            if roll < entry.weight {
+                return Some(entry.name.clone());
+            }
+            roll -= entry.weight;
+        }
+        None
+    }
+}
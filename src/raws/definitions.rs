@@ -0,0 +1,121 @@
+// definitions.rs
+//
+// Define the structure of the raw files, these mirror the JSON directly.
+use serde::Deserialize;
+
+use crate::systems::NORMAL_SPEED;
+use crate::StatusKind;
+
+/// The contents of a raw file
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Raws {
+    pub monsters: Vec<MonsterDef>,
+    pub items: Vec<ItemDef>,
+}
+
+/// How an entity is drawn, colours are HTML style hex codes
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RenderableDef {
+    pub glyph: String,
+    pub fg: String,
+    #[serde(default = "default_bg")]
+    pub bg: String,
+}
+
+fn default_bg() -> String { "#000000".to_string() }
+
+/// The combat statistics of a monster
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct StatsDef {
+    pub hp: i32,
+    pub power: i32,
+    pub defense: i32,
+}
+
+/// A type of monster
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct MonsterDef {
+    pub name: String,
+    pub renderable: RenderableDef,
+    pub stats: StatsDef,
+    pub vision: i32,
+    #[serde(default = "normal_speed")]
+    pub speed: i32,
+    #[serde(default)]
+    pub equipment: Vec<String>,
+    pub xp: i32,
+    pub spawn_weight: i32,
+    #[serde(default)]
+    pub depth_weight: i32,
+    pub min_depth: i32,
+    pub max_depth: Option<i32>,
+}
+
+/// Monsters that don't give a speed act as often as the player
+fn normal_speed() -> i32 { NORMAL_SPEED }
+
+/// The effects an item has when it is used
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct EffectsDef {
+    pub healing: Option<i32>,
+    pub nutrition: Option<i32>,
+    pub damage: Option<i32>,
+    pub confusion: Option<i32>,
+    pub poison: Option<i32>,
+    pub sleep: Option<i32>,
+    pub haste: Option<i32>,
+}
+
+impl EffectsDef {
+    /// Return the status effects, and how many turns they last for
+    pub fn statuses(&self) -> Vec<(StatusKind, i32)> {
+        [(StatusKind::Confused, self.confusion), (StatusKind::Poisoned, self.poison),
+         (StatusKind::Asleep, self.sleep), (StatusKind::Hasted, self.haste)].iter()
+            .filter_map(|(kind, turns)| turns.map(|turns| (*kind, turns)))
+            .collect()
+    }
+}
+
+/// How far away an item can be used, and the radius of the area it affects
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RangedDef {
+    pub range: i32,
+    #[serde(default)]
+    pub radius: i32,
+}
+
+/// The slot an item is worn in, and the bonuses it gives the wearer
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct EquippableDef {
+    pub slot: String,
+    #[serde(default)]
+    pub power: i32,
+    #[serde(default)]
+    pub defense: i32,
+}
+
+/// A type of item
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ItemDef {
+    pub name: String,
+    pub renderable: RenderableDef,
+    #[serde(default)]
+    pub consumable: bool,
+    #[serde(default)]
+    pub effects: EffectsDef,
+    pub ranged: Option<RangedDef>,
+    pub equippable: Option<EquippableDef>,
+    pub spawn_weight: i32,
+    #[serde(default)]
+    pub depth_weight: i32,
+    pub min_depth: i32,
+    pub max_depth: Option<i32>,
+}
@@ -0,0 +1,257 @@
+// mod.rs
+//
+// Load and validate the raw files that define the monsters and items.
+use std::collections::HashSet;
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::random_table::RandomTable;
+use crate::systems::ENERGY_THRESHOLD;
+use crate::EquipmentSlot;
+
+mod definitions;
+pub use definitions::*;
+
+/// The name of the raw file, used when reporting errors
+const SPAWNS_FILE: &str = "raws/spawns.json";
+
+/// The fastest a monster can be, so that even when hasted it never builds up more than a turn of energy at once
+const MAX_SPEED: i32 = ENERGY_THRESHOLD / 2;
+
+/// Resource holding the definitions loaded from the raw files
+pub struct RawMaster {
+    pub raws: Raws,
+}
+
+impl RawMaster {
+    /// Return the definition of the monster with the name given
+    pub fn monster(&self, name: &str) -> Option<&MonsterDef> {
+        self.raws.monsters.iter().find(|monster| monster.name == name)
+    }
+
+    /// Return the definition of the item with the name given
+    pub fn item(&self, name: &str) -> Option<&ItemDef> {
+        self.raws.items.iter().find(|item| item.name == name)
+    }
+
+    /// Return the table of everything that can be spawned at the given depth
+    pub fn spawn_table(&self, depth: i32) -> RandomTable {
+        let mut table = RandomTable::new();
+        for monster in self.raws.monsters.iter() {
+            table = table.add(&monster.name, weight_at(depth, monster.spawn_weight, monster.depth_weight, monster.min_depth, monster.max_depth));
+        }
+        for item in self.raws.items.iter() {
+            table = table.add(&item.name, weight_at(depth, item.spawn_weight, item.depth_weight, item.min_depth, item.max_depth));
+        }
+        table
+    }
+}
+
+/// Return the spawn weight at the depth, which changes by the depth weight for every level below the minimum
+fn weight_at(depth: i32, weight: i32, depth_weight: i32, min_depth: i32, max_depth: Option<i32>) -> i32 {
+    if depth < min_depth || max_depth.is_some_and(|max_depth| depth > max_depth) {
+        return 0;
+    }
+    i32::max(0, weight + depth_weight * (depth - min_depth))
+}
+
+/// Load the raw files that are embedded in the game, returning every problem found if they are not valid
+pub fn load() -> Result<RawMaster, Vec<String>> {
+    let contents = include_str!("../../raws/spawns.json");
+    parse(SPAWNS_FILE, contents)
+}
+
+/// Parse and validate the contents of a raw file
+pub fn parse(file: &str, contents: &str) -> Result<RawMaster, Vec<String>> {
+    let raws: Raws = serde_json::from_str(contents).map_err(|error| parse_errors(file, contents, error))?;
+
+    let mut errors = Vec::new();
+    let mut names = HashSet::new();
+    for monster in raws.monsters.iter() {
+        let mut problems = Vec::new();
+        validate_renderable(&monster.renderable, &mut problems);
+        validate_spawning(monster.spawn_weight, monster.min_depth, monster.max_depth, &mut problems);
+        if monster.stats.hp <= 0 { problems.push(format!("hp must be positive, not {}", monster.stats.hp)); }
+        if monster.stats.power < 0 { problems.push(format!("power cannot be negative, not {}", monster.stats.power)); }
+        if monster.stats.defense < 0 { problems.push(format!("defense cannot be negative, not {}", monster.stats.defense)); }
+        if monster.xp < 0 { problems.push(format!("xp cannot be negative, not {}", monster.xp)); }
+        if monster.vision <= 0 { problems.push(format!("vision must be positive, not {}", monster.vision)); }
+        if monster.speed <= 0 || monster.speed > MAX_SPEED {
+            problems.push(format!("speed must be between 1 and {}, not {}", MAX_SPEED, monster.speed));
+        }
+        let mut slots = Vec::new();
+        for name in monster.equipment.iter() {
+            let equippable = raws.items.iter().find(|item| item.name == *name)
+                .and_then(|item| item.equippable.as_ref());
+            match equippable.and_then(|equippable| EquipmentSlot::from_name(&equippable.slot)) {
+                Some(slot) if slots.contains(&slot) => {
+                    problems.push(format!("equipment '{}' is worn in the same slot as another item", name));
+                }
+                Some(slot) => slots.push(slot),
+                None => problems.push(format!("equipment '{}' is not an item that can be equipped", name))
+            }
+        }
+        if !names.insert(monster.name.clone()) { problems.push("the name is already in use".to_string()); }
+        errors.extend(problems.iter().map(|problem| format!("{}: monster '{}': {}", file, monster.name, problem)));
+    }
+    for item in raws.items.iter() {
+        let mut problems = Vec::new();
+        validate_renderable(&item.renderable, &mut problems);
+        validate_spawning(item.spawn_weight, item.min_depth, item.max_depth, &mut problems);
+        if let Some(healing) = item.effects.healing {
+            if healing <= 0 { problems.push(format!("healing must be positive, not {}", healing)); }
+        }
+        if let Some(nutrition) = item.effects.nutrition {
+            if nutrition <= 0 { problems.push(format!("nutrition must be positive, not {}", nutrition)); }
+        }
+        if let Some(damage) = item.effects.damage {
+            if damage <= 0 { problems.push(format!("damage must be positive, not {}", damage)); }
+        }
+        for (kind, turns) in item.effects.statuses() {
+            if turns <= 0 {
+                problems.push(format!("{} must last a positive number of turns, not {}", kind.name().to_lowercase(), turns));
+            }
+        }
+        if let Some(ranged) = &item.ranged {
+            if ranged.range <= 0 { problems.push(format!("range must be positive, not {}", ranged.range)); }
+            if ranged.radius < 0 { problems.push(format!("radius cannot be negative, not {}", ranged.radius)); }
+        }
+        if let Some(equippable) = &item.equippable {
+            if EquipmentSlot::from_name(&equippable.slot).is_none() {
+                problems.push(format!("'{}' is not an equipment slot", equippable.slot));
+            }
+            if equippable.power < 0 { problems.push(format!("power cannot be negative, not {}", equippable.power)); }
+            if equippable.defense < 0 {
+                problems.push(format!("defense cannot be negative, not {}", equippable.defense));
+            }
+            if item.consumable { problems.push("equipment cannot also be consumable".to_string()); }
+        }
+        if !names.insert(item.name.clone()) { problems.push("the name is already in use".to_string()); }
+        errors.extend(problems.iter().map(|problem| format!("{}: item '{}': {}", file, item.name, problem)));
+    }
+
+    if errors.is_empty() { Ok(RawMaster{ raws }) } else { Err(errors) }
+}
+
+/// Return the problems with a raw file that couldn't be parsed, each entry is parsed on its own so the ones at fault
+/// can be named, falling back to the error for the whole file when none of them are
+fn parse_errors(file: &str, contents: &str, error: serde_json::Error) -> Vec<String> {
+    let Ok(value) = serde_json::from_str::<Value>(contents) else { return vec![format!("{}: {}", file, error)] };
+    let mut errors = Vec::new();
+    entry_errors::<MonsterDef>(file, "monster", value.get("monsters"), &mut errors);
+    entry_errors::<ItemDef>(file, "item", value.get("items"), &mut errors);
+    if errors.is_empty() { errors.push(format!("{}: {}", file, error)); }
+    errors
+}
+
+/// Add the problems with any entries in the list that can't be parsed as the definition given
+fn entry_errors<T: DeserializeOwned>(file: &str, kind: &str, entries: Option<&Value>, errors: &mut Vec<String>) {
+    for (ndx, entry) in entries.and_then(Value::as_array).into_iter().flatten().enumerate() {
+        if let Err(error) = T::deserialize(entry) {
+            let name = entry.get("name").and_then(Value::as_str)
+                .map_or(format!("#{}", ndx + 1), |name| name.to_string());
+            errors.push(format!("{}: {} '{}': {}", file, kind, name, error));
+        }
+    }
+}
+
+/// Check that the glyph is a single character and the colours can be parsed
+fn validate_renderable(renderable: &RenderableDef, problems: &mut Vec<String>) {
+    if renderable.glyph.chars().count() != 1 {
+        problems.push(format!("glyph must be a single character, not '{}'", renderable.glyph));
+    }
+    for colour in [&renderable.fg, &renderable.bg] {
+        if let Err(error) = rltk::RGB::from_hex(colour) {
+            problems.push(format!("'{}' is not a valid colour ({:?})", colour, error));
+        }
+    }
+}
+
+/// Check that the spawn weight and depth range make sense
+fn validate_spawning(weight: i32, min_depth: i32, max_depth: Option<i32>, problems: &mut Vec<String>) {
+    if weight < 0 { problems.push(format!("spawn_weight cannot be negative, not {}", weight)); }
+    if min_depth < 1 { problems.push(format!("min_depth must be at least 1, not {}", min_depth)); }
+    if let Some(max_depth) = max_depth {
+        if max_depth < min_depth {
+            problems.push(format!("max_depth ({}) is less than min_depth ({})", max_depth, min_depth));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Return a raw file with one of each kind of entry, which is valid until the test changes it
+    fn valid_raws() -> Value {
+        json!({
+            "monsters": [{
+                "name": "Rat",
+                "renderable": { "glyph": "r", "fg": "#808080" },
+                "stats": { "hp": 2, "power": 1, "defense": 0 },
+                "vision": 4,
+                "xp": 1,
+                "spawn_weight": 1,
+                "min_depth": 1
+            }],
+            "items": [{
+                "name": "Ration",
+                "renderable": { "glyph": "%", "fg": "#C0A060" },
+                "consumable": true,
+                "effects": { "nutrition": 100 },
+                "spawn_weight": 1,
+                "min_depth": 1
+            }]
+        })
+    }
+
+    /// Return the errors found in the raw file, which must not be valid
+    fn errors(raws: &Value) -> Vec<String> {
+        parse("test.json", &raws.to_string()).err().expect("The raw file should not be valid")
+    }
+
+    #[test]
+    fn valid_raws_are_accepted() {
+        assert!(parse("test.json", &valid_raws().to_string()).is_ok());
+    }
+
+    #[test]
+    fn duplicate_names_are_reported() {
+        let mut raws = valid_raws();
+        raws["items"][0]["name"] = json!("Rat");
+        assert_eq!(errors(&raws), vec!["test.json: item 'Rat': the name is already in use"]);
+    }
+
+    #[test]
+    fn unknown_fields_are_reported() {
+        let mut raws = valid_raws();
+        raws["items"][0]["colour"] = json!("red");
+        let found = errors(&raws);
+        assert_eq!(found.len(), 1);
+        assert!(found[0].starts_with("test.json: item 'Ration': unknown field `colour`"), "{}", found[0]);
+    }
+
+    #[test]
+    fn speed_above_the_maximum_is_reported() {
+        let mut raws = valid_raws();
+        raws["monsters"][0]["speed"] = json!(MAX_SPEED + 1);
+        assert_eq!(errors(&raws), vec![format!("test.json: monster 'Rat': speed must be between 1 and {}, not {}",
+                                               MAX_SPEED, MAX_SPEED + 1)]);
+    }
+
+    #[test]
+    fn bad_spawn_weights_are_reported() {
+        let mut raws = valid_raws();
+        raws["monsters"][0]["spawn_weight"] = json!(-1);
+        raws["items"][0]["spawn_weight"] = json!("often");
+        let found = errors(&raws);
+        assert_eq!(found.len(), 1);
+        assert!(found[0].starts_with("test.json: item 'Ration': invalid type"), "{}", found[0]);
+
+        // Once everything parses the negative weight is caught by validation
+        raws["items"][0]["spawn_weight"] = json!(1);
+        assert_eq!(errors(&raws), vec!["test.json: monster 'Rat': spawn_weight cannot be negative, not -1"]);
+    }
+}
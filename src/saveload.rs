@@ -0,0 +1,268 @@
+// saveload.rs
+//
+// Saving and loading of the game world, entities are tracked via the SerializeMe marker.
+use std::convert::Infallible;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+
+use rltk::Point;
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs::saveload::{DeserializeComponents, MarkedBuilder, SerializeComponents, SimpleMarker, SimpleMarkerAllocator};
+
+use crate::{map_stats, register_components, AiState, AreaOfEffect, AwardsExperience, BlocksVisibility, CombatStats,
+            Consumable, DefenseBonus, Door, Equippable, Equipped, Experience, GameLog, HungerClock, InBackpack,
+            InflictsDamage, InflictsStatus, Initiative, Item, Map, MeleePowerBonus, Monster, OccupiesTile, Player,
+            Position, ProvidesFood, ProvidesHealing, Ranged, Renderable, RunStats, SerializationHelper, SerializeMe,
+            StatusEffects, Viewshed};
+
+/// The version of the save format, this must be incremented whenever the saved components change
+pub const SAVE_VERSION: u32 = 13;
+
+/// The location of the save file
+const SAVE_FILE: &str = "./savegame.json";
+
+/// Header written as the first line of the save file so the format can be checked before loading
+#[derive(Serialize, Deserialize)]
+struct SaveHeader {
+    game: String,
+    version: u32,
+}
+
+/// The ways that saving or loading the game can fail
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Format(String),
+    Version { found: u32, expected: u32 },
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(error) => write!(f, "unable to access {}: {}", SAVE_FILE, error),
+            SaveError::Format(message) => write!(f, "{} is not a valid save: {}", SAVE_FILE, message),
+            SaveError::Version { found, expected } =>
+                write!(f, "{} was saved with format version {}, but version {} is required", SAVE_FILE, found, expected),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<std::io::Error> for SaveError {
+    fn from(error: std::io::Error) -> Self { SaveError::Io(error) }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(error: serde_json::Error) -> Self { SaveError::Format(error.to_string()) }
+}
+
+macro_rules! serialize_individually {
+    ($ecs:expr, $ser:expr, $data:expr, $( $type:ty),*) => {
+        $(
+        SerializeComponents::<Infallible, SimpleMarker<SerializeMe>>::serialize(
+            &( $ecs.read_storage::<$type>(), ),
+            &$data.0,
+            &$data.1,
+            &mut $ser,
+        )?;
+        )*
+    };
+}
+
+macro_rules! deserialize_individually {
+    ($ecs:expr, $de:expr, $data:expr, $( $type:ty),*) => {
+        $(
+        DeserializeComponents::<Infallible, _>::deserialize(
+            &mut ( &mut $ecs.write_storage::<$type>(), ),
+            &$data.0,
+            &mut $data.1,
+            &mut $data.2,
+            &mut $de,
+        )?;
+        )*
+    };
+}
+
+/// Returns true if there is a save file on disk
+pub fn does_save_exist() -> bool {
+    Path::new(SAVE_FILE).exists()
+}
+
+/// Remove the save file, if there is one
+pub fn delete_save() -> std::io::Result<()> {
+    if does_save_exist() {
+        fs::remove_file(SAVE_FILE)?;
+    }
+    Ok(())
+}
+
+/// Save the world to disk
+pub fn save_game(ecs: &mut World) -> Result<(), SaveError> {
+    let file = File::create(SAVE_FILE)?;
+    save_to(ecs, file)
+}
+
+/// Save the world to the writer given
+fn save_to<W: Write>(ecs: &mut World, writer: W) -> Result<(), SaveError> {
+    // Temporarily wrap the map, statistics, and turn in an entity so they go along with everything else
+    let map_copy = ecs.get_mut::<Map>().unwrap().clone();
+    let stats = RunStats::clone(&ecs.fetch::<RunStats>());
+    let turn = ecs.fetch::<GameLog>().turn;
+    let helper = ecs.create_entity()
+        .with(SerializationHelper{ map: map_copy, stats, turn })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+
+    let result = write_save(ecs, writer);
+
+    // Clean-up the helper regardless of how the save went
+    ecs.delete_entity(helper).expect("Unable to delete the serialization helper");
+    result
+}
+
+/// Write the header and components of the save
+fn write_save<W: Write>(ecs: &World, mut writer: W) -> Result<(), SaveError> {
+    let header = SaveHeader { game: "NQAR".to_string(), version: SAVE_VERSION };
+    writeln!(writer, "{}", serde_json::to_string(&header)?)?;
+
+    let data = ( ecs.entities(), ecs.read_storage::<SimpleMarker<SerializeMe>>() );
+    let mut serializer = serde_json::Serializer::new(writer);
+    serialize_individually!(ecs, serializer, data, AiState, AreaOfEffect, AwardsExperience, BlocksVisibility,
+        CombatStats, Consumable, DefenseBonus, Door, Equippable, Equipped, Experience, HungerClock, InBackpack,
+        InflictsDamage, InflictsStatus, Initiative, Item, MeleePowerBonus, Monster, OccupiesTile, Player, Position,
+        ProvidesFood, ProvidesHealing, Ranged, Renderable, SerializationHelper, StatusEffects, Viewshed);
+    Ok(())
+}
+
+/// Replace the world with the one in the save file
+pub fn load_game(ecs: &mut World) -> Result<(), SaveError> {
+    let contents = fs::read_to_string(SAVE_FILE)?;
+    load_from_str(ecs, &contents)
+}
+
+/// The resources carried along with the entities in a save
+struct Restored {
+    map: Map,
+    stats: RunStats,
+    turn: u32,
+    player: (Entity, Point),
+}
+
+/// Replace the world with the one in the contents of a save, the world is left alone if the save can't be read
+fn load_from_str(ecs: &mut World, contents: &str) -> Result<(), SaveError> {
+    // Make sure the format is one we understand before touching the world
+    let (header, body) = contents.split_once('\n')
+        .ok_or_else(|| SaveError::Format("the header is missing".to_string()))?;
+    let header: SaveHeader = serde_json::from_str(header)?;
+    if header.game != "NQAR" {
+        return Err(SaveError::Format(format!("unexpected game '{}'", header.game)));
+    }
+    if header.version != SAVE_VERSION {
+        return Err(SaveError::Version { found: header.version, expected: SAVE_VERSION });
+    }
+
+    // Load everything in to a scratch world first, so a damaged body is caught before the current world is touched
+    let mut scratch = World::new();
+    register_components(&mut scratch);
+    scratch.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+    read_entities(&mut scratch, body)?;
+
+    // Now it is known to be good, replace the current world with it, what validation did to the map isn't saved
+    ecs.delete_all();
+    let restored = read_entities(ecs, body)?;
+    let mut map = restored.map;
+    map.tile_content = vec![Vec::new(); map.tiles.len()];
+    ecs.insert(map_stats(&map, restored.player.1));
+    ecs.insert(map);
+    ecs.insert(restored.stats);
+    ecs.write_resource::<GameLog>().turn = restored.turn;
+    ecs.insert(restored.player.0);
+    ecs.insert(restored.player.1);
+    Ok(())
+}
+
+/// Load the entities in the body of a save in to the world, and return the resources that were saved with them
+fn read_entities(ecs: &mut World, body: &str) -> Result<Restored, SaveError> {
+    // Load the entities, markers are remapped to new entities as they are created
+    {
+        let mut deserializer = serde_json::Deserializer::from_str(body);
+        let mut data = ( &mut ecs.entities(), &mut ecs.write_storage::<SimpleMarker<SerializeMe>>(),
+                         &mut ecs.write_resource::<SimpleMarkerAllocator<SerializeMe>>() );
+        deserialize_individually!(ecs, deserializer, data, AiState, AreaOfEffect, AwardsExperience, BlocksVisibility,
+            CombatStats, Consumable, DefenseBonus, Door, Equippable, Equipped, Experience, HungerClock, InBackpack,
+            InflictsDamage, InflictsStatus, Initiative, Item, MeleePowerBonus, Monster, OccupiesTile, Player, Position,
+            ProvidesFood, ProvidesHealing, Ranged, Renderable, SerializationHelper, StatusEffects, Viewshed);
+        deserializer.end()?;
+    }
+
+    // Take the map and other resources from the helper, the content index is rebuilt by the map indexing system
+    let helper = {
+        let entities = ecs.entities();
+        let helpers = ecs.read_storage::<SerializationHelper>();
+        (&entities, &helpers).join().map(|(entity, helper)| (entity, helper.clone())).next()
+    };
+    let (helper_entity, helper) = helper.ok_or_else(|| SaveError::Format("the map is missing".to_string()))?;
+    ecs.delete_entity(helper_entity).expect("Unable to delete the serialization helper");
+
+    // Find the player
+    let player = {
+        let entities = ecs.entities();
+        let players = ecs.read_storage::<Player>();
+        let positions = ecs.read_storage::<Position>();
+        (&entities, &players, &positions).join().map(|(entity, _player, pos)| (entity, Point::new(pos.x, pos.y))).next()
+    };
+    let player = player.ok_or_else(|| SaveError::Format("the player is missing".to_string()))?;
+    Ok(Restored { map: helper.map, stats: helper.stats, turn: helper.turn, player })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::State;
+
+    /// Return a new game, and the contents of a save of it
+    fn saved_game() -> (State, String) {
+        let mut gs = State::for_tests(1234);
+        gs.ecs.write_resource::<GameLog>().turn = 42;
+        let mut contents = Vec::new();
+        save_to(&mut gs.ecs, &mut contents).unwrap();
+        gs.ecs.write_resource::<GameLog>().turn = 0;
+        (gs, String::from_utf8(contents).unwrap())
+    }
+
+    /// Return the number of entities in the world
+    fn entity_count(ecs: &World) -> usize {
+        ecs.entities().join().count()
+    }
+
+    #[test]
+    fn round_trip() {
+        let (mut gs, contents) = saved_game();
+        let count = entity_count(&gs.ecs);
+        load_from_str(&mut gs.ecs, &contents).unwrap();
+        assert_eq!(entity_count(&gs.ecs), count);
+        assert_eq!(gs.ecs.fetch::<GameLog>().turn, 42);
+        assert!(gs.player_alive());
+    }
+
+    #[test]
+    fn truncated_body_leaves_the_world_alone() {
+        let (mut gs, contents) = saved_game();
+        let count = entity_count(&gs.ecs);
+        let truncated = &contents[.. contents.len() / 2];
+        assert!(matches!(load_from_str(&mut gs.ecs, truncated), Err(SaveError::Format(_))));
+        assert_eq!(entity_count(&gs.ecs), count);
+        assert!(gs.player_alive());
+    }
+
+    #[test]
+    fn wrong_version_is_rejected() {
+        let (mut gs, contents) = saved_game();
+        let contents = contents.replacen(&format!("\"version\":{}", SAVE_VERSION), "\"version\":0", 1);
+        assert!(matches!(load_from_str(&mut gs.ecs, &contents), Err(SaveError::Version { found: 0, .. })));
+    }
+}
@@ -0,0 +1,260 @@
+// spawner.rs
+//
+// Functions for spawning the player, monsters, and items in to the world.
+use std::collections::HashSet;
+
+use rltk::{Point, RandomNumberGenerator, RGB};
+use specs::prelude::*;
+use specs::saveload::{MarkedBuilder, SimpleMarker};
+
+use crate::raws::{ItemDef, MonsterDef, RawMaster, RenderableDef};
+use crate::systems::{NORMAL_SPEED, NORMAL_TURNS};
+use crate::{AiMode, AiState, AreaOfEffect, AwardsExperience, BlocksVisibility, CombatStats, Consumable, DefenseBonus,
+            Door, EquipmentSlot, Equippable, Equipped, Experience, HungerClock, HungerState, InflictsDamage,
+            InflictsStatus, Initiative, Item, Map, MeleePowerBonus, Monster, OccupiesTile, Player, Position,
+            ProvidesFood, ProvidesHealing, Ranged, Rectangle, Renderable, SerializeMe, StatusEffect, TileType, Viewshed,
+            DEFAULT_FOV};
+
+/// Create the player entity at the location given
+pub fn player(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('@'),
+            fg: RGB::named(rltk::YELLOW),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 0,
+        })
+        .with(Player{})
+        .with(OccupiesTile{})
+        .with(Viewshed { visible_tiles: Vec::new(), range: DEFAULT_FOV, dirty: true })
+        .with(CombatStats{ max_hp: 30, hp: 30, defense: 2, power: 5 })
+        .with(Initiative{ speed: NORMAL_SPEED, energy: 0 })
+        .with(HungerClock{ state: HungerState::Normal, turns: NORMAL_TURNS })
+        .with(Experience{ level: 1, xp: 0, perks_pending: 0 })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+/// How filling a corpse is for every hit point the monster had
+const CORPSE_NUTRITION: i32 = 10;
+
+/// Create the corpse of a slain monster at the location given, bigger monsters make for a better meal
+pub fn corpse(ecs: &mut World, x: i32, y: i32, name: &str, max_hp: i32) {
+    ecs.create_entity()
+        .with(Position{ x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('%'),
+            fg: RGB::named(rltk::DARK_RED),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Item{ name: format!("{} Corpse", name) })
+        .with(Consumable{})
+        .with(ProvidesFood{ nutrition: max_hp * CORPSE_NUTRITION })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+}
+
+/// Create a closed door at the location given
+pub fn door(ecs: &mut World, x: i32, y: i32) {
+    ecs.create_entity()
+        .with(Position{ x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('+'),
+            fg: RGB::named(rltk::CHOCOLATE),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 3,
+        })
+        .with(Door{ open: false })
+        .with(OccupiesTile{})
+        .with(BlocksVisibility{})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+}
+
+/// The most monsters and items that can be spawned in a single region on the first level
+const MAX_SPAWNS: i32 = 4;
+
+/// Fill the region with a random number of monsters and items suitable for the depth, each on a free floor tile. Tiles
+/// that are used are added to the set, and the number is used to tell monsters apart.
+pub fn spawn_region(ecs: &mut World, map: &Map, region: &Rectangle, depth: i32, used: &mut HashSet<usize>, number: &mut usize) {
+    // Pick what to spawn, and where, while the raws are borrowed
+    let mut spawns: Vec<(usize, String)> = Vec::new();
+    {
+        let raws = ecs.fetch::<RawMaster>();
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        let table = raws.spawn_table(depth);
+
+        // Only the interior of the region is floor for rooms, so do the same for everything else
+        let mut candidates: Vec<usize> = Vec::new();
+        for y in region.upper_y + 1 ..= region.lower_y {
+            for x in region.upper_x + 1 ..= region.lower_x {
+                if x < 0 || x >= map.width || y < 0 || y >= map.height { continue; }
+                let idx = map.xy_idx(x, y);
+                if map.tiles[idx] == TileType::Floor && !used.contains(&idx) {
+                    candidates.push(idx);
+                }
+            }
+        }
+
+        let count = (rng.roll_dice(1, MAX_SPAWNS + 3) + (depth - 1) - 3).max(0) as usize;
+        for _ in 0 .. count.min(candidates.len()) {
+            let ndx = rng.roll_dice(1, candidates.len() as i32) - 1;
+            let idx = candidates.swap_remove(ndx as usize);
+            if let Some(name) = table.roll(&mut rng) {
+                used.insert(idx);
+                spawns.push((idx, name));
+            }
+        }
+    }
+
+    // Now create the entities, the raws are checked for a monster first and then an item
+    for (idx, name) in spawns.iter() {
+        let (x, y) = (*idx as i32 % map.width, *idx as i32 / map.width);
+        let (monster, item) = {
+            let raws = ecs.fetch::<RawMaster>();
+            (raws.monster(name).cloned(), raws.item(name).cloned())
+        };
+        if let Some(monster) = monster {
+            *number += 1;
+            spawn_monster(ecs, &monster, x, y, *number);
+        } else if let Some(item) = item {
+            spawn_item(ecs, &item, x, y);
+        }
+    }
+}
+
+/// Convert the raw renderable in to the component, the raws have already been validated
+fn renderable(def: &RenderableDef, render_order: i32) -> Renderable {
+    Renderable {
+        glyph: rltk::to_cp437(def.glyph.chars().next().unwrap()),
+        fg: RGB::from_hex(&def.fg).unwrap(),
+        bg: RGB::from_hex(&def.bg).unwrap(),
+        render_order,
+    }
+}
+
+/// Create the monster defined at the location given
+fn spawn_monster(ecs: &mut World, def: &MonsterDef, x: i32, y: i32, number: usize) {
+    let monster = ecs.create_entity()
+        .with(Position{ x, y })
+        .with(renderable(&def.renderable, 1))
+        .with(Monster{
+            name: format!("{} #{}", &def.name, number),
+        })
+        .with(Viewshed{ visible_tiles : Vec::new(), range: def.vision, dirty: true })
+        .with(OccupiesTile{})
+        .with(CombatStats{ max_hp: def.stats.hp, hp: def.stats.hp, defense: def.stats.defense, power: def.stats.power })
+        .with(Initiative{ speed: def.speed, energy: 0 })
+        .with(AiState{ mode: AiMode::Wander, post: Point::new(x, y), last_seen: None })
+        .with(AwardsExperience{ xp: def.xp })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+
+    // Give the monster what it is wearing, the raws have already been checked that it can all be worn
+    for name in def.equipment.iter() {
+        let Some(item) = ecs.fetch::<RawMaster>().item(name).cloned() else { continue };
+        let slot = EquipmentSlot::from_name(&item.equippable.as_ref().unwrap().slot).unwrap();
+        build_item(ecs, &item)
+            .with(Equipped{ owner: monster, slot })
+            .marked::<SimpleMarker<SerializeMe>>()
+            .build();
+    }
+}
+
+/// Create the item defined at the location given
+fn spawn_item(ecs: &mut World, def: &ItemDef, x: i32, y: i32) {
+    build_item(ecs, def)
+        .with(Position{ x, y })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+}
+
+/// Start building the item defined, where it ends up is left to the caller
+fn build_item<'a>(ecs: &'a mut World, def: &ItemDef) -> EntityBuilder<'a> {
+    let mut builder = ecs.create_entity()
+        .with(renderable(&def.renderable, 2))
+        .with(Item{ name: def.name.clone() });
+
+    // Add the components for what the item does
+    if def.consumable {
+        builder = builder.with(Consumable{});
+    }
+    if let Some(heal_amount) = def.effects.healing {
+        builder = builder.with(ProvidesHealing{ heal_amount });
+    }
+    if let Some(nutrition) = def.effects.nutrition {
+        builder = builder.with(ProvidesFood{ nutrition });
+    }
+    if let Some(damage) = def.effects.damage {
+        builder = builder.with(InflictsDamage{ damage });
+    }
+    let statuses = def.effects.statuses();
+    if !statuses.is_empty() {
+        let effects = statuses.iter().map(|(kind, turns)| StatusEffect{ kind: *kind, turns: *turns }).collect();
+        builder = builder.with(InflictsStatus{ effects });
+    }
+    if let Some(ranged) = &def.ranged {
+        builder = builder.with(Ranged{ range: ranged.range });
+        if ranged.radius > 0 {
+            builder = builder.with(AreaOfEffect{ radius: ranged.radius });
+        }
+    }
+    if let Some(equippable) = &def.equippable {
+        let slot = EquipmentSlot::from_name(&equippable.slot).unwrap();
+        builder = builder.with(Equippable{ slot });
+        if equippable.power > 0 {
+            builder = builder.with(MeleePowerBonus{ power: equippable.power });
+        }
+        if equippable.defense > 0 {
+            builder = builder.with(DefenseBonus{ defense: equippable.defense });
+        }
+    }
+    builder
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::State;
+
+    /// Return the tiles that have more than one entity on them
+    fn stacked_tiles(ecs: &World) -> Vec<(i32, i32)> {
+        let mut seen = HashSet::new();
+        ecs.read_storage::<Position>().join()
+            .map(|pos| (pos.x, pos.y))
+            .filter(|tile| !seen.insert(*tile))
+            .collect()
+    }
+
+    #[test]
+    fn levels_never_have_two_entities_on_one_tile() {
+        for seed in 1..=5 {
+            let mut gs = State::for_tests(seed);
+            for depth in 1..=4 {
+                assert_eq!(stacked_tiles(&gs.ecs), Vec::new(), "seed {} depth {}", seed, depth);
+                gs.goto_next_level();
+            }
+        }
+    }
+
+    #[test]
+    fn crowded_regions_only_fill_the_free_tiles() {
+        // Deep enough that there is more to spawn than room for it, around the player so the start is in the way
+        let mut gs = State::for_tests(1234);
+        let map = Map::clone(&gs.ecs.fetch::<Map>());
+        let start = *gs.ecs.fetch::<Point>();
+        let region = Rectangle::new(start.x - 2, start.y - 2, 3, 3);
+        let mut used: HashSet<usize> = gs.ecs.read_storage::<Position>().join()
+            .map(|pos| map.xy_idx(pos.x, pos.y))
+            .collect();
+        let before = used.len();
+        let mut number = 0;
+        for _ in 0..10 {
+            spawn_region(&mut gs.ecs, &map, &region, 50, &mut used, &mut number);
+        }
+        assert!(used.len() > before);
+        assert_eq!(stacked_tiles(&gs.ecs), Vec::new());
+    }
+}
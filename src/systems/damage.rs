@@ -0,0 +1,125 @@
+// damage.rs
+//
+// Defines the system for applying damage, and the removal of the dead.
+use specs::prelude::*;
+
+use crate::{award_experience, spawner, AwardsExperience, CombatStats, Equipped, GameLog, Monster, Player, Position,
+            RunStats, StatusEffects, StatusKind, SufferDamage};
+
+pub struct DamageSystem {}
+
+impl<'a> System<'a> for DamageSystem {
+    type SystemData = ( WriteStorage<'a, CombatStats>,
+                        WriteStorage<'a, SufferDamage>,
+                        WriteStorage<'a, StatusEffects>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (mut stats, mut damage, mut statuses) = data;
+
+        for (stats, damage, statuses) in (&mut stats, &damage, (&mut statuses).maybe()).join() {
+            stats.hp -= damage.amount.iter().sum::<i32>();
+
+            // Being hurt is enough to wake anyone up
+            if let Some(statuses) = statuses {
+                statuses.effects.retain(|effect| effect.kind != StatusKind::Asleep);
+            }
+        }
+
+        // All damage has been applied
+        damage.clear();
+    }
+}
+
+/// Remove any entities that have died from the world
+pub fn delete_the_dead(ecs: &mut World) {
+    let mut dead: Vec<Entity> = Vec::new();
+    let mut corpses: Vec<(i32, i32, String, i32)> = Vec::new();
+    let mut xp = 0;
+
+    // Find the dead, scoped so the storages are released before deletion
+    {
+        let combat_stats = ecs.read_storage::<CombatStats>();
+        let monsters = ecs.read_storage::<Monster>();
+        let players = ecs.read_storage::<Player>();
+        let positions = ecs.read_storage::<Position>();
+        let awards = ecs.read_storage::<AwardsExperience>();
+        let entities = ecs.entities();
+        let mut log = ecs.write_resource::<GameLog>();
+        let mut run_stats = ecs.write_resource::<RunStats>();
+        for (entity, stats) in (&entities, &combat_stats).join() {
+            if stats.hp > 0 { continue; }
+            if players.get(entity).is_some() {
+                // The player is left in place for the game over screen
+                log.add("You are dead!");
+                continue;
+            }
+            if let Some(monster) = monsters.get(entity) {
+                log.add(format!("{} is dead.", monster.name));
+                run_stats.monsters_killed += 1;
+                xp += awards.get(entity).map_or(0, |award| award.xp);
+
+                // Monsters are told apart by a number after their name, which the corpse doesn't need
+                if let Some(pos) = positions.get(entity) {
+                    let name = monster.name.rsplit_once(" #").map_or(monster.name.as_str(), |(name, _)| name);
+                    corpses.push((pos.x, pos.y, name.to_string(), stats.max_hp));
+                }
+            }
+            dead.push(entity);
+        }
+    }
+
+    // Anything the dead were wearing falls to the floor where they were
+    {
+        let entities = ecs.entities();
+        let mut equipped = ecs.write_storage::<Equipped>();
+        let mut positions = ecs.write_storage::<Position>();
+        let dropped: Vec<(Entity, Entity)> = (&entities, &equipped).join()
+            .filter(|(_item, worn)| dead.contains(&worn.owner))
+            .map(|(item, worn)| (item, worn.owner))
+            .collect();
+        for (item, owner) in dropped {
+            equipped.remove(item);
+            let Some(Position { x, y }) = positions.get(owner).cloned() else { continue };
+            positions.insert(item, Position{ x, y }).expect("Unable to insert position");
+        }
+    }
+
+    // Remove them from the world
+    for victim in dead {
+        ecs.delete_entity(victim).expect("Unable to delete");
+    }
+
+    // Slain monsters leave something behind to eat
+    for (x, y, name, max_hp) in corpses {
+        spawner::corpse(ecs, x, y, &name, max_hp);
+    }
+
+    // Only the player fights monsters, so they get the experience for any that died
+    if xp > 0 {
+        award_experience(ecs, xp);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EquipmentSlot, Item, State};
+
+    #[test]
+    fn the_dead_drop_what_they_were_wearing() {
+        let mut gs = State::for_tests(1234);
+        let monster = gs.keep_monsters(1)[0];
+        let worn = gs.ecs.create_entity()
+            .with(Item{ name: "Dagger".to_string() })
+            .with(Equipped{ owner: monster, slot: EquipmentSlot::Weapon })
+            .build();
+        let pos = gs.ecs.read_storage::<Position>().get(monster).cloned().unwrap();
+        gs.ecs.write_storage::<CombatStats>().get_mut(monster).unwrap().hp = 0;
+
+        delete_the_dead(&mut gs.ecs);
+        assert!(!gs.ecs.entities().is_alive(monster));
+        assert!(gs.ecs.read_storage::<Equipped>().get(worn).is_none());
+        let dropped = gs.ecs.read_storage::<Position>().get(worn).cloned().unwrap();
+        assert_eq!((dropped.x, dropped.y), (pos.x, pos.y));
+    }
+}
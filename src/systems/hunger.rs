@@ -0,0 +1,53 @@
+// hunger.rs
+//
+// Defines the system that makes everyone that needs to eat hungrier as their turns go by.
+use specs::prelude::*;
+
+use crate::{GameLog, HungerClock, HungerState, MyTurn, SufferDamage};
+
+/// How many turns each state lasts before moving on to the next, being well fed lasts as long as the food eaten
+pub const NORMAL_TURNS: i32 = 300;
+const HUNGRY_TURNS: i32 = 200;
+
+/// The damage taken each turn while starving
+const STARVATION_DAMAGE: i32 = 1;
+
+pub struct HungerSystem {}
+
+impl<'a> System<'a> for HungerSystem {
+    type SystemData = ( ReadExpect<'a, Entity>,
+                        WriteExpect<'a, GameLog>,
+                        Entities<'a>,
+                        WriteStorage<'a, HungerClock>,
+                        ReadStorage<'a, MyTurn>,
+                        WriteStorage<'a, SufferDamage>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (player_entity, mut log, entities, mut clocks, turns, mut suffer_damage) = data;
+
+        // Hunger counts down on the entity's own turns
+        for (entity, clock, _turn) in (&entities, &mut clocks, &turns).join() {
+            let is_player = entity == *player_entity;
+            if clock.state == HungerState::Starving {
+                SufferDamage::new_damage(&mut suffer_damage, entity, STARVATION_DAMAGE);
+                if is_player {
+                    log.add(format!("Your hunger pangs are getting painful, you suffer {} hp.", STARVATION_DAMAGE));
+                }
+                continue;
+            }
+
+            clock.turns -= 1;
+            if clock.turns > 0 { continue; }
+            let (state, turns, message) = match clock.state {
+                HungerState::WellFed => (HungerState::Normal, NORMAL_TURNS, "You are no longer well fed."),
+                HungerState::Normal => (HungerState::Hungry, HUNGRY_TURNS, "You are hungry."),
+                _ => (HungerState::Starving, 0, "You are starving!"),
+            };
+            clock.state = state;
+            clock.turns = turns;
+            if is_player {
+                log.add(message);
+            }
+        }
+    }
+}
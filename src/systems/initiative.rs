@@ -0,0 +1,88 @@
+// initiative.rs
+//
+// Defines the system that decides who gets to act next, based upon how much energy they have built up.
+use specs::prelude::*;
+
+use crate::{Initiative, MyTurn, StatusEffects, StatusKind};
+
+/// The energy an entity needs to act, entities gain their speed in energy every tick
+pub const ENERGY_THRESHOLD: i32 = 1000;
+
+/// The speed of the player, and any monster that doesn't say otherwise
+pub const NORMAL_SPEED: i32 = 100;
+
+pub struct InitiativeSystem {}
+
+impl<'a> System<'a> for InitiativeSystem {
+    type SystemData = ( Entities<'a>,
+                        WriteStorage<'a, Initiative>,
+                        WriteStorage<'a, MyTurn>,
+                        ReadStorage<'a, StatusEffects>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, mut initiative, mut turns, statuses) = data;
+
+        // Everyone has had their turn
+        turns.clear();
+
+        // No one would ever build up enough energy to act, so don't wait for it
+        if !(&initiative).join().any(|initiative| initiative.speed > 0) {
+            return;
+        }
+
+        // Keep building up energy until at least one entity can act, hasted entities build it up twice as fast
+        let mut acting = false;
+        while !acting {
+            for (entity, initiative) in (&entities, &mut initiative).join() {
+                let hasted = statuses.get(entity).is_some_and(|statuses| statuses.has(StatusKind::Hasted));
+                initiative.energy += if hasted { initiative.speed * 2 } else { initiative.speed };
+                if initiative.energy >= ENERGY_THRESHOLD {
+                    initiative.energy -= ENERGY_THRESHOLD;
+                    turns.insert(entity, MyTurn{}).expect("Unable to insert turn");
+                    acting = true;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Return a world with entities of the speeds given
+    fn world_with_speeds(speeds: &[i32]) -> World {
+        let mut ecs = World::new();
+        ecs.register::<Initiative>();
+        ecs.register::<MyTurn>();
+        ecs.register::<StatusEffects>();
+        for speed in speeds {
+            ecs.create_entity().with(Initiative{ speed: *speed, energy: 0 }).build();
+        }
+        ecs
+    }
+
+    #[test]
+    fn returns_when_no_one_has_initiative() {
+        let ecs = world_with_speeds(&[]);
+        InitiativeSystem{}.run_now(&ecs);
+        assert_eq!(ecs.read_storage::<MyTurn>().join().count(), 0);
+    }
+
+    #[test]
+    fn returns_when_no_one_has_speed() {
+        let ecs = world_with_speeds(&[0, 0]);
+        InitiativeSystem{}.run_now(&ecs);
+        assert_eq!(ecs.read_storage::<MyTurn>().join().count(), 0);
+    }
+
+    #[test]
+    fn faster_entities_act_first() {
+        let ecs = world_with_speeds(&[NORMAL_SPEED, NORMAL_SPEED * 2]);
+        InitiativeSystem{}.run_now(&ecs);
+        let speeds: Vec<i32> = (&ecs.read_storage::<Initiative>(), &ecs.read_storage::<MyTurn>()).join()
+            .map(|(initiative, _turn)| initiative.speed)
+            .collect();
+        assert_eq!(speeds, vec![NORMAL_SPEED * 2]);
+    }
+}
@@ -0,0 +1,238 @@
+// inventory.rs
+//
+// Defines the systems for picking up, using, and dropping items.
+use rltk::{field_of_view, Point};
+use specs::prelude::*;
+
+use super::name_of;
+use crate::{AreaOfEffect, CombatStats, Consumable, Equippable, Equipped, GameLog, HungerClock, HungerState, InBackpack,
+            InflictsDamage, InflictsStatus, Item, Map, Monster, Position, ProvidesFood, ProvidesHealing, RunStats,
+            StatusEffects, SufferDamage, WantsToDropItem, WantsToPickupItem, WantsToRemoveItem, WantsToUseItem};
+
+pub struct ItemCollectionSystem {}
+
+impl<'a> System<'a> for ItemCollectionSystem {
+    type SystemData = ( ReadExpect<'a, Entity>,
+                        WriteExpect<'a, GameLog>,
+                        WriteStorage<'a, WantsToPickupItem>,
+                        WriteStorage<'a, Position>,
+                        ReadStorage<'a, Item>,
+                        WriteStorage<'a, InBackpack>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (player_entity, mut log, mut wants_pickup, mut positions, items, mut backpack) = data;
+
+        for pickup in wants_pickup.join() {
+            // Move the item from the map in to the backpack
+            positions.remove(pickup.item);
+            backpack.insert(pickup.item, InBackpack{ owner: pickup.collected_by }).expect("Unable to insert backpack entry");
+
+            if pickup.collected_by == *player_entity {
+                log.add(format!("You pick up the {}.", items.get(pickup.item).unwrap().name));
+            }
+        }
+
+        // All pickups have been resolved
+        wants_pickup.clear();
+    }
+}
+
+pub struct ItemUseSystem {}
+
+impl<'a> System<'a> for ItemUseSystem {
+    type SystemData = ( ReadExpect<'a, Entity>,
+                        WriteExpect<'a, GameLog>,
+                        WriteExpect<'a, RunStats>,
+                        ReadExpect<'a, Map>,
+                        Entities<'a>,
+                        WriteStorage<'a, WantsToUseItem>,
+                        ReadStorage<'a, Item>,
+                        ReadStorage<'a, Monster>,
+                        ReadStorage<'a, Position>,
+                        ReadStorage<'a, Consumable>,
+                        ReadStorage<'a, ProvidesHealing>,
+                        ReadStorage<'a, ProvidesFood>,
+                        ReadStorage<'a, InflictsDamage>,
+                        ReadStorage<'a, AreaOfEffect>,
+                        ReadStorage<'a, InflictsStatus>,
+                        WriteStorage<'a, CombatStats>,
+                        WriteStorage<'a, SufferDamage>,
+                        WriteStorage<'a, StatusEffects>,
+                        WriteStorage<'a, HungerClock>,
+                        ReadStorage<'a, Equippable>,
+                        WriteStorage<'a, Equipped>,
+                        WriteStorage<'a, InBackpack>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (player_entity, mut log, mut run_stats, map, entities, mut wants_use, items, monsters, positions,
+             consumables, healing, food, inflicts_damage, area_of_effect, inflicts_status, mut combat_stats,
+             mut suffer_damage, mut statuses, mut hunger_clocks, equippables, mut equipped, mut backpack) = data;
+
+        for (entity, use_item) in (&entities, &wants_use).join() {
+            let item_name = &items.get(use_item.item).unwrap().name;
+
+            // Equipment is worn rather than used, and takes the place of anything already worn in the same slot
+            if let Some(equippable) = equippables.get(use_item.item) {
+                let worn: Vec<Entity> = (&entities, &equipped).join()
+                    .filter(|(_item, worn)| worn.owner == entity && worn.slot == equippable.slot)
+                    .map(|(item, _worn)| item)
+                    .collect();
+                for item in worn {
+                    equipped.remove(item);
+                    backpack.insert(item, InBackpack{ owner: entity }).expect("Unable to insert backpack entry");
+                    if entity == *player_entity {
+                        log.add(format!("You unequip the {}.", items.get(item).unwrap().name));
+                    }
+                }
+                backpack.remove(use_item.item);
+                equipped.insert(use_item.item, Equipped{ owner: entity, slot: equippable.slot })
+                    .expect("Unable to insert equipped entry");
+                if entity == *player_entity {
+                    log.add(format!("You equip the {}.", item_name));
+                }
+                continue;
+            }
+
+            // Work out who is affected, ranged items hit whatever they land on and anything caught in the blast
+            let mut used = false;
+            let targets = match (use_item.target, positions.get(entity)) {
+                (Some(target), Some(pos)) => {
+                    used = true;
+                    let impact = map.line_of_fire(Point::new(pos.x, pos.y), target);
+                    let mut blast = vec![impact];
+                    if let Some(aoe) = area_of_effect.get(use_item.item) {
+                        blast = field_of_view(impact, aoe.radius, &*map);
+                        blast.retain(|tile| map.in_bounds(tile.x, tile.y));
+                    }
+                    blast.iter()
+                        .flat_map(|tile| map.tile_content[map.xy_idx(tile.x, tile.y)].iter().copied())
+                        .filter(|target| combat_stats.contains(*target))
+                        .collect()
+                }
+                _ => vec![entity]
+            };
+
+            // Apply any healing the item provides
+            if let Some(healer) = healing.get(use_item.item) {
+                for target in targets.iter() {
+                    if let Some(stats) = combat_stats.get_mut(*target) {
+                        stats.hp = i32::min(stats.max_hp, stats.hp + healer.heal_amount);
+                        if *target == *player_entity {
+                            log.add(format!("You use the {}, healing {} hp.", item_name, healer.heal_amount));
+                        }
+                        used = true;
+                    }
+                }
+            }
+
+            // Eating fills up anyone that gets hungry
+            if let Some(food) = food.get(use_item.item) {
+                for target in targets.iter() {
+                    if let Some(clock) = hunger_clocks.get_mut(*target) {
+                        clock.state = HungerState::WellFed;
+                        clock.turns = food.nutrition;
+                        if *target == *player_entity {
+                            log.add(format!("You eat the {}.", item_name));
+                        }
+                        used = true;
+                    }
+                }
+            }
+
+            // Apply any damage the item does
+            if let Some(damage) = inflicts_damage.get(use_item.item) {
+                for target in targets.iter() {
+                    SufferDamage::new_damage(&mut suffer_damage, *target, damage.damage);
+                    log.add(format!("The {} hits {}, for {} hp.", item_name, name_of(&monsters, *target), damage.damage));
+                }
+            }
+            // Apply any status effects the item causes
+            if let Some(inflicts) = inflicts_status.get(use_item.item) {
+                for target in targets.iter() {
+                    for effect in inflicts.effects.iter() {
+                        StatusEffects::new_effect(&mut statuses, *target, effect.kind, effect.turns);
+                        log.add(format!("{} is {}.", name_of(&monsters, *target), effect.kind.name().to_lowercase()));
+                    }
+                    used = true;
+                }
+            }
+            if targets.is_empty() {
+                log.add(format!("The {} hits nothing.", item_name));
+            }
+
+            if used && entity == *player_entity {
+                run_stats.items_used += 1;
+            }
+
+            // Consumables are gone once they have been used
+            if used && consumables.get(use_item.item).is_some() {
+                entities.delete(use_item.item).expect("Unable to delete item");
+            }
+        }
+
+        // All uses have been resolved
+        wants_use.clear();
+    }
+}
+
+pub struct ItemRemoveSystem {}
+
+impl<'a> System<'a> for ItemRemoveSystem {
+    type SystemData = ( ReadExpect<'a, Entity>,
+                        WriteExpect<'a, GameLog>,
+                        Entities<'a>,
+                        WriteStorage<'a, WantsToRemoveItem>,
+                        ReadStorage<'a, Item>,
+                        WriteStorage<'a, Equipped>,
+                        WriteStorage<'a, InBackpack>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (player_entity, mut log, entities, mut wants_remove, items, mut equipped, mut backpack) = data;
+
+        for (entity, to_remove) in (&entities, &wants_remove).join() {
+            // Take the item off and put it back in the backpack
+            equipped.remove(to_remove.item);
+            backpack.insert(to_remove.item, InBackpack{ owner: entity }).expect("Unable to insert backpack entry");
+
+            if entity == *player_entity {
+                log.add(format!("You unequip the {}.", items.get(to_remove.item).unwrap().name));
+            }
+        }
+
+        // All removals have been resolved
+        wants_remove.clear();
+    }
+}
+
+pub struct ItemDropSystem {}
+
+impl<'a> System<'a> for ItemDropSystem {
+    type SystemData = ( ReadExpect<'a, Entity>,
+                        WriteExpect<'a, GameLog>,
+                        Entities<'a>,
+                        WriteStorage<'a, WantsToDropItem>,
+                        ReadStorage<'a, Item>,
+                        WriteStorage<'a, Position>,
+                        WriteStorage<'a, InBackpack>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (player_entity, mut log, entities, mut wants_drop, items, mut positions, mut backpack) = data;
+
+        for (entity, to_drop) in (&entities, &wants_drop).join() {
+            // Place the item where the entity is standing
+            let dropper = match positions.get(entity) {
+                Some(pos) => Position{ x: pos.x, y: pos.y },
+                None => continue
+            };
+            positions.insert(to_drop.item, dropper).expect("Unable to insert position");
+            backpack.remove(to_drop.item);
+
+            if entity == *player_entity {
+                log.add(format!("You drop the {}.", items.get(to_drop.item).unwrap().name));
+            }
+        }
+
+        // All drops have been resolved
+        wants_drop.clear();
+    }
+}
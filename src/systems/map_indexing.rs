@@ -2,22 +2,45 @@
 //
 // Defines the system for map indexing.
 use specs::prelude::*;
-use crate::{Map, OccupiesTile, Position};
+use crate::{BlocksVisibility, Door, Map, OccupiesTile, Position};
 
 pub struct MapIndexingSystem {}
 
 impl<'a> System<'a> for MapIndexingSystem {
-    type SystemData = ( WriteExpect<'a, Map>,
+    type SystemData = ( Entities<'a>,
+                        WriteExpect<'a, Map>,
                         ReadStorage<'a, Position>,
-                        ReadStorage<'a, OccupiesTile>);
+                        ReadStorage<'a, OccupiesTile>,
+                        ReadStorage<'a, BlocksVisibility>,
+                        ReadStorage<'a, Door>);
 
     fn run(&mut self, data : Self::SystemData) {
-        let (mut map, position, occupiers) = data;
+        let (entities, mut map, position, occupiers, blockers, doors) = data;
 
         map.populate_occupied();
-        for (position, _blocks) in (&position, &occupiers).join() {
+        map.clear_view_blocked();
+        map.clear_closed_doors();
+        map.clear_content_index();
+        for (entity, position) in (&entities, &position).join() {
             let idx = map.xy_idx(position.x, position.y);
-            map.occupied_tiles[idx] = true;
+
+            // Mark the tile as occupied if the entity blocks it
+            if occupiers.get(entity).is_some() {
+                map.occupied_tiles[idx] = true;
+            }
+
+            // Mark the tile as opaque if the entity blocks the view
+            if blockers.get(entity).is_some() {
+                map.view_blocked[idx] = true;
+            }
+
+            // Mark the tile as a closed door, which can be opened by moving in to it
+            if doors.get(entity).is_some_and(|door| !door.open) {
+                map.closed_doors[idx] = true;
+            }
+
+            // Note the entity in the index for the tile
+            map.tile_content[idx].push(entity);
         }
     }
 }
\ No newline at end of file
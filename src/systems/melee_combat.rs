@@ -0,0 +1,120 @@
+// melee_combat.rs
+//
+// Defines the system for resolving melee attacks.
+use specs::prelude::*;
+
+use super::name_of;
+use crate::{CombatStats, DefenseBonus, Equipped, GameLog, MeleePowerBonus, Monster, SufferDamage, WantsToMelee};
+
+pub struct MeleeCombatSystem {}
+
+impl<'a> System<'a> for MeleeCombatSystem {
+    type SystemData = ( Entities<'a>,
+                        WriteExpect<'a, GameLog>,
+                        WriteStorage<'a, WantsToMelee>,
+                        ReadStorage<'a, Monster>,
+                        ReadStorage<'a, CombatStats>,
+                        WriteStorage<'a, SufferDamage>,
+                        ReadStorage<'a, Equipped>,
+                        ReadStorage<'a, MeleePowerBonus>,
+                        ReadStorage<'a, DefenseBonus>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, mut log, mut wants_melee, monsters, combat_stats, mut inflict_damage, equipped, power_bonuses,
+             defense_bonuses) = data;
+
+        for (entity, wants_melee, stats) in (&entities, &wants_melee, &combat_stats).join() {
+            // The dead don't get to attack
+            if stats.hp <= 0 { continue; }
+
+            // Nor do they get attacked
+            let target_stats = combat_stats.get(wants_melee.target);
+            if let Some(target_stats) = target_stats {
+                if target_stats.hp <= 0 { continue; }
+
+                // Resolve the attack, with whatever the attacker and defender are wearing helping them out
+                let attacker = name_of(&monsters, entity);
+                let defender = name_of(&monsters, wants_melee.target);
+                let power = stats.power + worn_bonus(&equipped, &power_bonuses, entity, |bonus| bonus.power);
+                let defense = target_stats.defense
+                    + worn_bonus(&equipped, &defense_bonuses, wants_melee.target, |bonus| bonus.defense);
+                let damage = i32::max(0, power - defense);
+                if damage == 0 {
+                    log.add(format!("{} is unable to hurt {}.", attacker, defender));
+                } else {
+                    log.add(format!("{} hits {}, for {} hp.", attacker, defender, damage));
+                    SufferDamage::new_damage(&mut inflict_damage, wants_melee.target, damage);
+                }
+            }
+        }
+
+        // All attacks have been resolved
+        wants_melee.clear();
+    }
+}
+
+/// Return the total bonus from the equipment the entity is wearing
+fn worn_bonus<T: Component>(equipped: &ReadStorage<Equipped>, bonuses: &ReadStorage<T>, owner: Entity,
+                            amount: impl Fn(&T) -> i32) -> i32 {
+    (equipped, bonuses).join()
+        .filter(|(worn, _bonus)| worn.owner == owner)
+        .map(|(_worn, bonus)| amount(bonus))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{register_components, EquipmentSlot};
+
+    /// Return a world with a fighter of the power and defense given
+    fn world_with_fighters(fighters: &[(i32, i32)]) -> (World, Vec<Entity>) {
+        let mut ecs = World::new();
+        register_components(&mut ecs);
+        ecs.insert(GameLog::default());
+        let entities = fighters.iter()
+            .map(|(power, defense)| ecs.create_entity()
+                .with(CombatStats{ max_hp: 10, hp: 10, power: *power, defense: *defense })
+                .build())
+            .collect();
+        (ecs, entities)
+    }
+
+    /// Return the damage the entity is going to suffer
+    fn damage_to(ecs: &World, entity: Entity) -> Vec<i32> {
+        ecs.read_storage::<SufferDamage>().get(entity).map_or(Vec::new(), |damage| damage.amount.clone())
+    }
+
+    #[test]
+    fn worn_equipment_adds_to_power_and_defense() {
+        let (mut ecs, fighters) = world_with_fighters(&[(5, 0), (0, 1)]);
+        let (attacker, defender) = (fighters[0], fighters[1]);
+        ecs.create_entity()
+            .with(MeleePowerBonus{ power: 3 })
+            .with(Equipped{ owner: attacker, slot: EquipmentSlot::Weapon })
+            .build();
+        ecs.create_entity()
+            .with(DefenseBonus{ defense: 2 })
+            .with(Equipped{ owner: defender, slot: EquipmentSlot::Shield })
+            .build();
+
+        // Equipment that isn't worn does nothing
+        ecs.create_entity().with(MeleePowerBonus{ power: 100 }).build();
+
+        ecs.write_storage::<WantsToMelee>().insert(attacker, WantsToMelee{ target: defender }).unwrap();
+        MeleeCombatSystem{}.run_now(&ecs);
+        assert_eq!(damage_to(&ecs, defender), vec![5 + 3 - 1 - 2]);
+    }
+
+    #[test]
+    fn defense_can_stop_all_the_damage() {
+        let (mut ecs, fighters) = world_with_fighters(&[(2, 0), (0, 1)]);
+        ecs.create_entity()
+            .with(DefenseBonus{ defense: 4 })
+            .with(Equipped{ owner: fighters[1], slot: EquipmentSlot::Body })
+            .build();
+        ecs.write_storage::<WantsToMelee>().insert(fighters[0], WantsToMelee{ target: fighters[1] }).unwrap();
+        MeleeCombatSystem{}.run_now(&ecs);
+        assert_eq!(damage_to(&ecs, fighters[1]), Vec::<i32>::new());
+    }
+}
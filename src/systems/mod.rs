@@ -1,7 +1,31 @@
 // mod.rs
 //
 // Define what systems are exposed.
+use specs::prelude::*;
+
+use crate::Monster;
+
+mod damage;
+pub use damage::{delete_the_dead, DamageSystem};
+mod hunger;
+pub use hunger::{HungerSystem, NORMAL_TURNS};
+mod initiative;
+pub use initiative::{InitiativeSystem, ENERGY_THRESHOLD, NORMAL_SPEED};
+mod inventory;
+pub use inventory::{ItemCollectionSystem, ItemDropSystem, ItemRemoveSystem, ItemUseSystem};
 mod map_indexing;
 pub use map_indexing::MapIndexingSystem;
+mod melee_combat;
+pub use melee_combat::MeleeCombatSystem;
+mod status;
+pub use status::StatusEffectSystem;
 mod visibility;
 pub use visibility::VisibilitySystem;
+
+/// Return the name of the entity, anything that isn't a monster is presumed to be the player
+fn name_of(monsters: &ReadStorage<Monster>, entity: Entity) -> String {
+    match monsters.get(entity) {
+        Some(monster) => monster.name.clone(),
+        None => "Player".to_string()
+    }
+}
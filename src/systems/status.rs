@@ -0,0 +1,53 @@
+// status.rs
+//
+// Defines the system that applies status effects and counts down how long they last.
+use specs::prelude::*;
+
+use super::name_of;
+use crate::{CombatStats, GameLog, Map, Monster, MyTurn, Position, StatusEffects, StatusKind, SufferDamage};
+
+/// The damage taken each turn while poisoned
+const POISON_DAMAGE: i32 = 1;
+
+pub struct StatusEffectSystem {}
+
+impl<'a> System<'a> for StatusEffectSystem {
+    type SystemData = ( ReadExpect<'a, Entity>,
+                        ReadExpect<'a, Map>,
+                        WriteExpect<'a, GameLog>,
+                        Entities<'a>,
+                        WriteStorage<'a, StatusEffects>,
+                        ReadStorage<'a, MyTurn>,
+                        ReadStorage<'a, Monster>,
+                        ReadStorage<'a, Position>,
+                        ReadStorage<'a, CombatStats>,
+                        WriteStorage<'a, SufferDamage>);
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (player_entity, map, mut log, entities, mut statuses, turns, monsters, positions, combat_stats,
+             mut suffer_damage) = data;
+
+        // Effects last for a number of the entity's own turns
+        for (entity, statuses, _turn, pos) in (&entities, &mut statuses, &turns, &positions).join() {
+            // Only report on what the player can see
+            let seen = entity == *player_entity || map.visible_tiles[map.xy_idx(pos.x, pos.y)];
+
+            // Poison hurts every turn
+            if statuses.has(StatusKind::Poisoned) && combat_stats.get(entity).is_some() {
+                SufferDamage::new_damage(&mut suffer_damage, entity, POISON_DAMAGE);
+                if seen {
+                    log.add(format!("{} suffers {} hp from poison.", name_of(&monsters, entity), POISON_DAMAGE));
+                }
+            }
+
+            // Count down the effects, and remove the ones that have worn off
+            for effect in statuses.effects.iter_mut() {
+                effect.turns -= 1;
+                if effect.turns <= 0 && seen {
+                    log.add(format!("{} is no longer {}.", name_of(&monsters, entity), effect.kind.name().to_lowercase()));
+                }
+            }
+            statuses.effects.retain(|effect| effect.turns > 0);
+        }
+    }
+}
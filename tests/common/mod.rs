@@ -0,0 +1,9 @@
+// mod.rs
+//
+// Fixtures shared by the integration tests.
+use nqar::{raws, BuilderType, State, MAP_HEIGHT, MAP_WIDTH};
+
+/// Return a new game from the seed given, using the default map size and raw files, and the builder if one is given
+pub fn new_game(seed: u64, builder: Option<BuilderType>) -> State {
+    State::new(seed, builder, None, (MAP_WIDTH, MAP_HEIGHT), raws::load().expect("Unable to load the raw files"))
+}
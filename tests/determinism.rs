@@ -0,0 +1,40 @@
+// determinism.rs
+//
+// Check that the same seed always builds the same world, whichever map builder is used.
+mod common;
+
+use nqar::{BuilderType, Item, Map, Monster, Position, State};
+use specs::prelude::*;
+
+const BUILDERS: [BuilderType; 4] =
+    [BuilderType::SimpleRooms, BuilderType::Bsp, BuilderType::CellularAutomata, BuilderType::DrunkardsWalk];
+
+/// Return the bounds of the rooms on the map
+fn rooms(gs: &State) -> Vec<(i32, i32, i32, i32)> {
+    gs.ecs.fetch::<Map>().rooms.iter().map(|room| (room.upper_x, room.upper_y, room.lower_x, room.lower_y)).collect()
+}
+
+/// Return where everything that was spawned is, and what it is called
+fn spawns(gs: &State) -> Vec<(i32, i32, String)> {
+    let positions = gs.ecs.read_storage::<Position>();
+    let monsters = gs.ecs.read_storage::<Monster>();
+    let items = gs.ecs.read_storage::<Item>();
+    let mut spawns: Vec<(i32, i32, String)> = (&positions, &monsters).join()
+        .map(|(pos, monster)| (pos.x, pos.y, monster.name.clone()))
+        .collect();
+    spawns.extend((&positions, &items).join().map(|(pos, item)| (pos.x, pos.y, item.name.clone())));
+    spawns
+}
+
+#[test]
+fn same_seed_builds_the_same_world() {
+    for builder in BUILDERS {
+        for seed in [1, 1234, 987_654_321] {
+            let (first, second) = (common::new_game(seed, Some(builder)), common::new_game(seed, Some(builder)));
+            assert!(first.ecs.fetch::<Map>().tiles == second.ecs.fetch::<Map>().tiles, "{:?} seed {}", builder, seed);
+            assert_eq!(rooms(&first), rooms(&second), "{:?} seed {}", builder, seed);
+            assert_eq!(spawns(&first), spawns(&second), "{:?} seed {}", builder, seed);
+            assert!(!spawns(&first).is_empty(), "{:?} seed {}", builder, seed);
+        }
+    }
+}
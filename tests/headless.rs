@@ -0,0 +1,44 @@
+// headless.rs
+//
+// Play scripted games through the headless driver, the same way --headless does.
+mod common;
+
+use nqar::{headless, GameLog, State};
+use rltk::Point;
+
+/// Play the script in a new game from the seed given, and return the game and the turns taken
+fn play(seed: u64, script: &str) -> (State, usize) {
+    let actions = headless::parse_script(script).unwrap();
+    let mut gs = common::new_game(seed, None);
+    let turns = headless::run(&mut gs, &actions);
+    (gs, turns)
+}
+
+#[test]
+fn script_runs_to_the_end() {
+    let (gs, turns) = play(1234, ".... hjkl yubn ....");
+    assert_eq!(turns, 16);
+    assert!(gs.player_alive());
+    assert!(gs.ecs.fetch::<GameLog>().turn > 0);
+}
+
+#[test]
+fn script_plays_out_the_same_every_time() {
+    let script = "llll jjjj hhhh kkkk ........";
+    let (first, first_turns) = play(42, script);
+    let (second, second_turns) = play(42, script);
+    assert_eq!(first_turns, second_turns);
+    assert_eq!(*first.ecs.fetch::<Point>(), *second.ecs.fetch::<Point>());
+    assert_eq!(first.ecs.fetch::<GameLog>().turn, second.ecs.fetch::<GameLog>().turn);
+    let messages = |gs: &State| -> Vec<String> {
+        gs.ecs.fetch::<GameLog>().entries.iter().map(|entry| entry.message.clone()).collect()
+    };
+    assert_eq!(messages(&first), messages(&second));
+}
+
+#[test]
+fn invalid_scripts_are_rejected() {
+    assert!(headless::parse_script("llx").is_err());
+    assert!(headless::parse_script("i").is_err());
+    assert_eq!(headless::parse_script("l i0 d1 r2").unwrap().len(), 4);
+}